@@ -0,0 +1,6 @@
+-- Backs the "recently updated" query (updated_at > now() - window).
+-- A partial index can't be used here because its predicate would need now(),
+-- which isn't immutable. BRIN only pays off when updated_at follows the
+-- physical row order, and updated rows land wherever there's free space.
+-- A plain B-tree it is.
+CREATE INDEX IF NOT EXISTS products_updated_at_idx ON products (updated_at DESC);
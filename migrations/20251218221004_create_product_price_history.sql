@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS product_price_history (
+  id BIGSERIAL PRIMARY KEY,
+  product_id UUID NOT NULL REFERENCES products (id) ON DELETE CASCADE,
+  old_price INT NOT NULL,
+  new_price INT NOT NULL,
+  changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS product_price_history_product_id_idx
+  ON product_price_history (product_id, changed_at);
@@ -0,0 +1,32 @@
+ALTER TABLE products ADD COLUMN IF NOT EXISTS slug TEXT;
+
+-- Backfill existing rows with the same rules as domain::slug::slugify.
+-- Duplicates get part of their id appended instead of a counter, so they
+-- can't collide with a name that naturally ends in "-2".
+WITH slugs AS (
+  SELECT
+    id,
+    COALESCE(
+      NULLIF(
+        trim(BOTH '-' FROM regexp_replace(
+          regexp_replace(regexp_replace(lower(name), '[\s_-]', '-', 'g'), '[^a-z0-9-]', '', 'g'),
+          '-+', '-', 'g'
+        )),
+        ''
+      ),
+      'product'
+    ) AS base
+  FROM products
+  WHERE slug IS NULL
+), ranked AS (
+  SELECT id, base, row_number() OVER (PARTITION BY base ORDER BY created_at, id) AS n
+  FROM slugs JOIN products USING (id)
+)
+UPDATE products
+SET slug = CASE WHEN ranked.n = 1 THEN ranked.base ELSE ranked.base || '-' || left(products.id::text, 8) END
+FROM ranked
+WHERE products.id = ranked.id;
+
+ALTER TABLE products ALTER COLUMN slug SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS products_slug_idx ON products (slug);
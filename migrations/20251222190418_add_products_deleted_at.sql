@@ -0,0 +1,3 @@
+-- Deleted products are kept, with the time they were deleted, so their
+-- price history survives and they can be restored.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ NULL;
@@ -0,0 +1,3 @@
+-- Bumped on every write so clients can make their updates conditional on
+-- the version they read (If-Match).
+ALTER TABLE products ADD COLUMN IF NOT EXISTS version INT NOT NULL DEFAULT 1;
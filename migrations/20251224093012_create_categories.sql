@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS categories (
+  id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+  name TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS categories_name_idx ON categories (lower(name));
+
+-- RESTRICT: a category can't be deleted while any product, deleted ones
+-- included, still points at it.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS category_id UUID NULL
+  REFERENCES categories (id) ON DELETE RESTRICT;
+
+CREATE INDEX IF NOT EXISTS products_category_id_idx ON products (category_id);
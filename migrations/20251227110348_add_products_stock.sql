@@ -0,0 +1,4 @@
+-- The CHECK backs up the conditional decrement in reserve: stock can never
+-- go negative, even if some other writer forgets the guard.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS stock INT NOT NULL DEFAULT 0
+  CONSTRAINT products_stock_non_negative CHECK (stock >= 0);
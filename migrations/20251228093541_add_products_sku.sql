@@ -0,0 +1,4 @@
+-- Optional, since existing products have none; unique among those that do.
+ALTER TABLE products ADD COLUMN IF NOT EXISTS sku TEXT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS products_sku_idx ON products (sku);
@@ -0,0 +1,5 @@
+-- Backs keyset pagination, (updated_at, id) < ($1, $2) ORDER BY updated_at
+-- DESC, id DESC. Its leading column serves the "recently updated" query just
+-- as well, so the single-column index goes.
+CREATE INDEX IF NOT EXISTS products_updated_at_id_idx ON products (updated_at DESC, id DESC);
+DROP INDEX IF EXISTS products_updated_at_idx;
@@ -0,0 +1,5 @@
+-- Only live products need distinct SKUs; a deleted product's SKU can be
+-- reused, and restoring it while another product holds the SKU conflicts.
+DROP INDEX IF EXISTS products_sku_idx;
+
+CREATE UNIQUE INDEX IF NOT EXISTS products_sku_idx ON products (sku) WHERE deleted_at IS NULL;
@@ -0,0 +1,126 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    application::{
+        product_service::ClassifyError,
+        validation::{self, InvalidField},
+    },
+    domain::category::{Category, CategoryId},
+};
+
+pub trait CategoryRepository {
+    type Error: ClassifyError + 'static;
+
+    /// A name already taken, ignoring case, is an `is_conflict` error.
+    fn create(&self, name: String) -> impl Future<Output = Result<Category, Self::Error>> + Send;
+
+    /// Every category, by name.
+    fn read_all(&self) -> impl Future<Output = Result<Vec<Category>, Self::Error>> + Send;
+
+    fn read_one(
+        &self,
+        id: CategoryId,
+    ) -> impl Future<Output = Result<Option<Category>, Self::Error>> + Send;
+
+    fn rename(
+        &self,
+        id: CategoryId,
+        name: String,
+    ) -> impl Future<Output = Result<Option<Category>, Self::Error>> + Send;
+
+    /// `false` if there's no category with `id`. Deleted products are taken
+    /// out of the category; if live ones still belong to it, it's left alone,
+    /// with an `is_conflict` error.
+    fn delete(&self, id: CategoryId) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+}
+
+#[derive(Debug)]
+pub enum CategoryServiceError<E> {
+    NotFound,
+    /// The input was rejected before reaching the repository.
+    Validation(InvalidField),
+    /// Another category already has the name.
+    DuplicateName,
+    /// Live products still belong to the category.
+    InUse,
+    Repository(E),
+}
+impl<E: fmt::Display> fmt::Display for CategoryServiceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "category not found"),
+            Self::Validation(invalid) => write!(f, "{}", invalid),
+            Self::DuplicateName => write!(f, "category name is taken"),
+            Self::InUse => write!(f, "category still has products"),
+            Self::Repository(error) => write!(f, "repository error: {}", error),
+        }
+    }
+}
+impl<E: Error + 'static> Error for CategoryServiceError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Repository(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Name clashes are the only conflict a create or rename can run into.
+fn name_conflict<E: ClassifyError>(error: E) -> CategoryServiceError<E> {
+    if error.is_conflict() {
+        CategoryServiceError::DuplicateName
+    } else {
+        CategoryServiceError::Repository(error)
+    }
+}
+
+pub struct CategoryService<R: CategoryRepository> {
+    repo: R,
+}
+impl<R: CategoryRepository> CategoryService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn add(&self, name: String) -> Result<Category, CategoryServiceError<R::Error>> {
+        let name = validation::name(name).map_err(CategoryServiceError::Validation)?;
+        self.repo.create(name).await.map_err(name_conflict)
+    }
+
+    pub async fn list(&self) -> Result<Vec<Category>, R::Error> {
+        self.repo.read_all().await
+    }
+
+    pub async fn find(&self, id: CategoryId) -> Result<Category, CategoryServiceError<R::Error>> {
+        self.repo
+            .read_one(id)
+            .await
+            .map_err(CategoryServiceError::Repository)?
+            .ok_or(CategoryServiceError::NotFound)
+    }
+
+    pub async fn rename(
+        &self,
+        id: CategoryId,
+        name: String,
+    ) -> Result<Category, CategoryServiceError<R::Error>> {
+        let name = validation::name(name).map_err(CategoryServiceError::Validation)?;
+        self.repo
+            .rename(id, name)
+            .await
+            .map_err(name_conflict)?
+            .ok_or(CategoryServiceError::NotFound)
+    }
+
+    /// Refuses to delete a category live products still belong to, rather
+    /// than silently moving them out of it. Deleted products don't hold it
+    /// up; they come back uncategorized if restored.
+    pub async fn remove(&self, id: CategoryId) -> Result<(), CategoryServiceError<R::Error>> {
+        match self.repo.delete(id).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(CategoryServiceError::NotFound),
+            Err(error) if error.is_conflict() => Err(CategoryServiceError::InUse),
+            Err(error) => Err(CategoryServiceError::Repository(error)),
+        }
+    }
+}
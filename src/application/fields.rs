@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+
+use crate::domain::product::Product;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    String,
+    Integer,
+    Timestamp,
+}
+impl FieldKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Timestamp => "timestamp",
+        }
+    }
+}
+
+/// A product field's value, comparable with values of the same kind.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub enum FieldValue<'a> {
+    String(&'a str),
+    Integer(i64),
+    Timestamp(DateTime<Utc>),
+}
+
+/// A product field clients may refer to in list queries.
+#[derive(Debug)]
+pub struct Field {
+    /// Both the query-parameter name and the database column.
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub sortable: bool,
+    /// Integer fields only: filterable through `min_<name>`/`max_<name>`.
+    pub filterable: bool,
+    pub value: for<'a> fn(&'a Product) -> FieldValue<'a>,
+}
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+impl Eq for Field {}
+
+/// Everything that sorting, filtering and the capabilities endpoint know
+/// about product fields. Making a field sortable or filterable is a change to
+/// this table alone.
+pub static PRODUCT_FIELDS: &[Field] = &[
+    Field {
+        name: "name",
+        kind: FieldKind::String,
+        sortable: true,
+        filterable: false,
+        value: |product| FieldValue::String(&product.name),
+    },
+    Field {
+        name: "price",
+        kind: FieldKind::Integer,
+        sortable: true,
+        filterable: true,
+        value: |product| FieldValue::Integer(product.price.into()),
+    },
+    Field {
+        name: "created_at",
+        kind: FieldKind::Timestamp,
+        sortable: true,
+        filterable: false,
+        value: |product| FieldValue::Timestamp(product.created_at),
+    },
+    Field {
+        name: "updated_at",
+        kind: FieldKind::Timestamp,
+        sortable: true,
+        filterable: false,
+        value: |product| FieldValue::Timestamp(product.updated_at),
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Field> {
+    PRODUCT_FIELDS.iter().find(|field| field.name == name)
+}
@@ -0,0 +1,158 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    application::fields::{self, Field, FieldKind, FieldValue},
+    domain::{category::CategoryId, product::Product},
+};
+
+/// Inclusive bounds on an integer field. A `None` leaves that side
+/// unconstrained.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeFilter {
+    pub field: &'static Field,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+/// Narrows a product listing. An empty filter matches everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProductFilter {
+    pub ranges: Vec<RangeFilter>,
+    /// Case-insensitive substring of the name, matched literally.
+    pub search: Option<String>,
+    pub category_id: Option<CategoryId>,
+    /// Exact, case-sensitive tag.
+    pub tag: Option<String>,
+}
+impl ProductFilter {
+    /// Trimmed like stored tags are; a blank tag doesn't narrow anything.
+    pub fn with_tag(mut self, tag: Option<String>) -> Self {
+        self.tag = tag
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty());
+        self
+    }
+
+    pub fn with_category(mut self, category_id: Option<CategoryId>) -> Self {
+        self.category_id = category_id;
+        self
+    }
+
+    /// An empty query doesn't narrow anything.
+    pub fn with_search(mut self, query: Option<String>) -> Self {
+        self.search = query.filter(|query| !query.is_empty());
+        self
+    }
+
+    /// Adds `min_<field>`/`max_<field>` bounds, if either is given.
+    pub fn with_range(
+        mut self,
+        field: &str,
+        min: Option<i64>,
+        max: Option<i64>,
+    ) -> Result<Self, InvalidFilter> {
+        if min.is_none() && max.is_none() {
+            return Ok(self);
+        }
+
+        let field = fields::lookup(field)
+            .filter(|field| field.filterable && field.kind == FieldKind::Integer)
+            .ok_or_else(|| InvalidFilter::Field(field.to_owned()))?;
+        if let (Some(min), Some(max)) = (min, max)
+            && min > max
+        {
+            return Err(InvalidFilter::Range {
+                field: field.name,
+                min,
+                max,
+            });
+        }
+
+        self.ranges.push(RangeFilter { field, min, max });
+        Ok(self)
+    }
+
+    /// In-memory equivalent of the repository's `WHERE` clause.
+    pub fn matches(&self, product: &Product) -> bool {
+        let found = self
+            .search
+            .as_ref()
+            .is_none_or(|query| product.name.to_lowercase().contains(&query.to_lowercase()));
+
+        found
+            && self
+                .category_id
+                .is_none_or(|id| product.category_id == Some(id))
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| product.tags.contains(tag))
+            && self.ranges.iter().all(|range| {
+                let value = (range.field.value)(product);
+                range
+                    .min
+                    .is_none_or(|min| value >= FieldValue::Integer(min))
+                    && range
+                        .max
+                        .is_none_or(|max| value <= FieldValue::Integer(max))
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvalidFilter {
+    Field(String),
+    Range {
+        field: &'static str,
+        min: i64,
+        max: i64,
+    },
+}
+impl fmt::Display for InvalidFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Field(field) => write!(f, "cannot filter by '{}'", field),
+            Self::Range { field, min, max } => write!(
+                f,
+                "min_{0} ({1}) must not be greater than max_{0} ({2})",
+                field, min, max
+            ),
+        }
+    }
+}
+impl Error for InvalidFilter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_inverted_range() {
+        let filter = ProductFilter::default();
+        assert!(
+            filter
+                .clone()
+                .with_range("price", Some(10), Some(10))
+                .is_ok()
+        );
+        assert!(filter.clone().with_range("price", Some(10), None).is_ok());
+        assert_eq!(
+            filter.with_range("price", Some(20), Some(10)),
+            Err(InvalidFilter::Range {
+                field: "price",
+                min: 20,
+                max: 10
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unregistered_and_unfilterable_fields() {
+        for field in ["stock", "name"] {
+            assert_eq!(
+                ProductFilter::default().with_range(field, Some(1), None),
+                Err(InvalidFilter::Field(field.into()))
+            );
+        }
+    }
+}
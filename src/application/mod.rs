@@ -1 +1,7 @@
+pub mod category_service;
+pub mod fields;
+pub mod filtering;
+pub mod pagination;
 pub mod product_service;
+pub mod sorting;
+pub mod validation;
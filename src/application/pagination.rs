@@ -0,0 +1,145 @@
+use std::{error::Error, fmt};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::domain::product::{Product, ProductId};
+
+/// A window into an ordered listing, with the limit already clamped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Page {
+    pub limit: u32,
+    pub offset: u32,
+    /// Starts the page right after this product instead of counting rows,
+    /// so inserts and deletes don't shift it. Only meaningful in the default
+    /// order, most recently updated first.
+    pub after: Option<Cursor>,
+}
+impl Page {
+    pub const DEFAULT_LIMIT: u32 = 20;
+    pub const MAX_LIMIT: u32 = 100;
+
+    /// Fills in defaults and clamps `limit` to `1..=MAX_LIMIT`, so callers
+    /// should report the resulting values back rather than the requested ones.
+    pub fn new(limit: Option<u32>, offset: Option<u32>) -> Self {
+        Self {
+            limit: limit
+                .unwrap_or(Self::DEFAULT_LIMIT)
+                .clamp(1, Self::MAX_LIMIT),
+            offset: offset.unwrap_or(0),
+            after: None,
+        }
+    }
+
+    pub fn with_after(mut self, after: Option<Cursor>) -> Self {
+        self.after = after;
+        self
+    }
+}
+impl Default for Page {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+/// Where a keyset page ends: the `(updated_at, id)` of its last product.
+/// Clients get it as an opaque string and send it back as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub updated_at: DateTime<Utc>,
+    pub id: ProductId,
+}
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!(
+            "{},{}",
+            self.updated_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.id
+        );
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, InvalidCursor> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(InvalidCursor)?;
+        let (updated_at, id) = raw.split_once(',').ok_or(InvalidCursor)?;
+
+        Ok(Self {
+            updated_at: DateTime::parse_from_rfc3339(updated_at)
+                .map_err(|_| InvalidCursor)?
+                .to_utc(),
+            id: id.parse().map_err(|_| InvalidCursor)?,
+        })
+    }
+}
+impl From<&Product> for Cursor {
+    fn from(value: &Product) -> Self {
+        Self {
+            updated_at: value.updated_at,
+            id: value.id,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidCursor;
+impl fmt::Display for InvalidCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid cursor; pass back a next_cursor as is")
+    }
+}
+impl Error for InvalidCursor {}
+
+/// One page of a listing together with the size of the whole listing.
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page: Page,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_defaults_and_clamps_limit() {
+        assert_eq!(
+            Page::new(None, None),
+            Page {
+                limit: 20,
+                offset: 0,
+                after: None
+            }
+        );
+        assert_eq!(
+            Page::new(Some(50), Some(10)),
+            Page {
+                limit: 50,
+                offset: 10,
+                after: None
+            }
+        );
+        assert_eq!(Page::new(Some(1000), None).limit, Page::MAX_LIMIT);
+        assert_eq!(Page::new(Some(0), None).limit, 1);
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = Cursor {
+            updated_at: DateTime::parse_from_rfc3339("2025-01-02T03:04:05.123456Z")
+                .unwrap()
+                .to_utc(),
+            id: "1f0e4c6a-8a4b-4f7e-9a53-2b3c4d5e6f70".parse().unwrap(),
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Ok(cursor));
+        assert_eq!(Cursor::decode("not a cursor"), Err(InvalidCursor));
+        assert_eq!(
+            Cursor::decode(&URL_SAFE_NO_PAD.encode("yesterday,42")),
+            Err(InvalidCursor)
+        );
+    }
+}
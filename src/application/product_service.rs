@@ -1,41 +1,238 @@
-use std::error::Error;
+use std::{error::Error, fmt, time::Duration};
+
+use futures_util::Stream;
+
+use crate::{
+    application::{
+        filtering::ProductFilter,
+        pagination::{Page, Paged},
+        sorting::Sort,
+        validation::{self, InvalidField},
+    },
+    domain::{
+        category::CategoryId,
+        price::Price,
+        product::{NewProduct, PriceChange, Product, ProductId, ProductStats},
+        sku, tags,
+    },
+};
+
+/// Lets handlers tell failure modes apart without knowing the backend's
+/// error type.
+pub trait ClassifyError: Error {
+    /// The backend gave up on a slow operation; retrying later may succeed.
+    fn is_timeout(&self) -> bool {
+        false
+    }
+
+    /// The write clashes with existing data, e.g. a unique constraint.
+    fn is_conflict(&self) -> bool {
+        false
+    }
 
-use uuid::Uuid;
+    /// The write expected a version of the record that's no longer current.
+    fn is_stale(&self) -> bool {
+        false
+    }
 
-use crate::domain::product::Product;
+    /// The write tried to change a value that's fixed once set.
+    fn is_immutable(&self) -> bool {
+        false
+    }
+}
 
 pub trait ProductRepository {
-    type Error: Error;
+    type Error: ClassifyError + 'static;
 
     fn create(
         &self,
         name: String,
         description: String,
-        price: u32,
+        price: Price,
+        tags: Vec<String>,
+        sku: Option<String>,
     ) -> impl Future<Output = Result<Product, Self::Error>> + Send;
 
+    /// Creates all of `products` or, if any of them fails, none.
+    fn create_many(
+        &self,
+        products: Vec<NewProduct>,
+    ) -> impl Future<Output = Result<Vec<Product>, Self::Error>> + Send;
+
     fn read_all(&self) -> impl Future<Output = Result<Vec<Product>, Self::Error>> + Send;
 
+    /// Every product that isn't soft-deleted, oldest first, yielded as it's
+    /// read. Unlike `read_all` there's no row ceiling, since nothing has to
+    /// hold the whole table at once.
+    fn stream_all(&self) -> impl Stream<Item = Result<Product, Self::Error>> + Send + '_;
+
+    /// Like `read_all`, but only products matching `filter`, in `sort` order
+    /// and only `page` of them. Soft-deleted products are left out unless
+    /// `include_deleted` is set. A page larger than the row ceiling fails the
+    /// same way `read_all` does.
+    fn read_sorted(
+        &self,
+        filter: &ProductFilter,
+        sort: Sort,
+        page: Page,
+        include_deleted: bool,
+    ) -> impl Future<Output = Result<Vec<Product>, Self::Error>> + Send;
+
+    /// Like `read_sorted`, but every matching product, yielded as it's read
+    /// instead of collected into a page.
+    fn stream_sorted<'a>(
+        &'a self,
+        filter: &'a ProductFilter,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> impl Stream<Item = Result<Product, Self::Error>> + Send + 'a;
+
+    fn count(
+        &self,
+        filter: &ProductFilter,
+        include_deleted: bool,
+    ) -> impl Future<Output = Result<u64, Self::Error>> + Send;
+
+    fn stats(&self) -> impl Future<Output = Result<ProductStats, Self::Error>> + Send;
+
+    /// A soft-deleted product is only returned with `include_deleted`.
     fn read_one(
         &self,
-        id: Uuid,
+        id: ProductId,
+        include_deleted: bool,
+    ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
+
+    fn read_by_slug(
+        &self,
+        slug: &str,
     ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
 
+    /// Takes the SKU as stored, i.e. already normalized.
+    fn find_by_sku(
+        &self,
+        sku: &str,
+    ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
+
+    fn read_updated_within(
+        &self,
+        within: Duration,
+    ) -> impl Future<Output = Result<Vec<Product>, Self::Error>> + Send;
+
+    /// With `expected_version`, fails with an `is_stale` error instead of
+    /// writing if the product has moved on to another version. `sku` is set
+    /// if the product has none yet; changing one it has is an `is_immutable`
+    /// error, and `None` keeps it.
     fn update(
         &self,
-        id: Uuid,
-        name: String,
-        description: String,
-        price: u32,
+        id: ProductId,
+        product: NewProduct,
+        expected_version: Option<i32>,
+    ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
+
+    /// Like `update`, but a `None` field keeps its current value.
+    fn patch(
+        &self,
+        id: ProductId,
+        name: Option<String>,
+        description: Option<String>,
+        price: Option<Price>,
+        tags: Option<Vec<String>>,
+        expected_version: Option<i32>,
+    ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
+
+    /// Soft-deletes the product; `false` if there's no live product with `id`.
+    fn delete(&self, id: ProductId) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+
+    /// Soft-deletes every live product among `ids` in one go, returning how
+    /// many that was. Unknown ids are skipped.
+    fn delete_many(
+        &self,
+        ids: &[ProductId],
+    ) -> impl Future<Output = Result<u64, Self::Error>> + Send;
+
+    /// Undoes `delete`; `None` if there's no deleted product with `id`. A
+    /// product whose category was deleted meanwhile comes back without one;
+    /// one whose SKU a live product took meanwhile is an `is_conflict` error.
+    /// Whether `updated_at` moves is up to the implementation.
+    fn restore(
+        &self,
+        id: ProductId,
     ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
 
-    fn delete(&self, id: Uuid) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+    /// Moves the product into `category_id`, or out of any category with
+    /// `None`. A category that doesn't exist is an `is_conflict` error.
+    fn set_category(
+        &self,
+        id: ProductId,
+        category_id: Option<CategoryId>,
+    ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
+
+    fn set_stock(
+        &self,
+        id: ProductId,
+        stock: u32,
+    ) -> impl Future<Output = Result<Option<Product>, Self::Error>> + Send;
+
+    /// Takes `quantity` units out of stock in one conditional write, so
+    /// concurrent reservations can't oversell.
+    fn reserve(
+        &self,
+        id: ProductId,
+        quantity: u32,
+    ) -> impl Future<Output = Result<Reservation, Self::Error>> + Send;
+
+    /// Price changes recorded by `update`, oldest first.
+    fn read_price_history(
+        &self,
+        id: ProductId,
+    ) -> impl Future<Output = Result<Vec<PriceChange>, Self::Error>> + Send;
+}
+
+pub enum Reservation {
+    Reserved(Box<Product>),
+    /// Nothing was taken; only `available` units are left.
+    Insufficient {
+        available: u32,
+    },
+    NotFound,
 }
 
+#[derive(Debug)]
 pub enum ProductServiceError<E> {
     NotFound,
+    /// The input was rejected before reaching the repository.
+    Validation(InvalidField),
+    InsufficientStock {
+        available: u32,
+    },
     Repository(E),
 }
+impl<E: fmt::Display> fmt::Display for ProductServiceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "product not found"),
+            Self::Validation(message) => write!(f, "{}", message),
+            Self::InsufficientStock { available } => {
+                write!(f, "only {} in stock", available)
+            }
+            Self::Repository(error) => write!(f, "repository error: {}", error),
+        }
+    }
+}
+impl<E: Error + 'static> Error for ProductServiceError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NotFound | Self::Validation(_) | Self::InsufficientStock { .. } => None,
+            Self::Repository(error) => Some(error),
+        }
+    }
+}
+
+fn validate_sku<E>(sku: String) -> Result<String, ProductServiceError<E>> {
+    sku::normalize(&sku).ok_or_else(|| {
+        ProductServiceError::Validation(InvalidField::new("sku", "sku must not be empty"))
+    })
+}
 
 pub struct ProductService<R: ProductRepository> {
     repo: R,
@@ -49,18 +246,87 @@ impl<R: ProductRepository> ProductService<R> {
         &self,
         name: String,
         description: String,
-        price: u32,
-    ) -> Result<Product, R::Error> {
-        self.repo.create(name, description, price).await
+        price: Price,
+        tags: Vec<String>,
+        sku: Option<String>,
+    ) -> Result<Product, ProductServiceError<R::Error>> {
+        let name = validation::name(name).map_err(ProductServiceError::Validation)?;
+        let sku = sku.map(validate_sku).transpose()?;
+        self.repo
+            .create(name, description, price, tags::normalize(tags), sku)
+            .await
+            .map_err(ProductServiceError::Repository)
+    }
+
+    /// Checks and normalizes every product like `add` does before creating
+    /// anything, so an import either lands whole or not at all.
+    pub async fn import(
+        &self,
+        products: Vec<NewProduct>,
+    ) -> Result<Vec<Product>, ProductServiceError<R::Error>> {
+        let products = products
+            .into_iter()
+            .map(|product| {
+                Ok(NewProduct {
+                    name: validation::name(product.name)
+                        .map_err(ProductServiceError::Validation)?,
+                    tags: tags::normalize(product.tags),
+                    sku: product.sku.map(validate_sku).transpose()?,
+                    ..product
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        self.repo
+            .create_many(products)
+            .await
+            .map_err(ProductServiceError::Repository)
+    }
+
+    /// Fetches the page and the total count concurrently.
+    pub async fn list(
+        &self,
+        filter: &ProductFilter,
+        sort: Sort,
+        page: Page,
+        include_deleted: bool,
+    ) -> Result<Paged<Product>, R::Error> {
+        let (items, total) = tokio::try_join!(
+            self.repo.read_sorted(filter, sort, page, include_deleted),
+            self.repo.count(filter, include_deleted)
+        )?;
+        Ok(Paged { items, total, page })
+    }
+
+    /// Unlike `list`, there's no page and no total: every match is yielded.
+    pub fn stream<'a>(
+        &'a self,
+        filter: &'a ProductFilter,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> impl Stream<Item = Result<Product, R::Error>> + 'a {
+        self.repo.stream_sorted(filter, sort, include_deleted)
+    }
+
+    pub fn export(&self) -> impl Stream<Item = Result<Product, R::Error>> + '_ {
+        self.repo.stream_all()
+    }
+
+    pub async fn stats(&self) -> Result<ProductStats, R::Error> {
+        self.repo.stats().await
     }
 
-    pub async fn list(&self) -> Result<Vec<Product>, R::Error> {
-        self.repo.read_all().await
+    pub async fn list_recent(&self, within: Duration) -> Result<Vec<Product>, R::Error> {
+        self.repo.read_updated_within(within).await
     }
 
-    pub async fn find(&self, id: Uuid) -> Result<Product, ProductServiceError<R::Error>> {
+    pub async fn find(
+        &self,
+        id: ProductId,
+        include_deleted: bool,
+    ) -> Result<Product, ProductServiceError<R::Error>> {
         self.repo
-            .read_one(id)
+            .read_one(id, include_deleted)
             .await
             .map_err(ProductServiceError::Repository)
             .and_then(|opt| {
@@ -72,15 +338,84 @@ impl<R: ProductRepository> ProductService<R> {
             })
     }
 
+    pub async fn find_by_slug(&self, slug: &str) -> Result<Product, ProductServiceError<R::Error>> {
+        self.repo
+            .read_by_slug(slug)
+            .await
+            .map_err(ProductServiceError::Repository)
+            .and_then(|opt| {
+                if let Some(product) = opt {
+                    Ok(product)
+                } else {
+                    Err(ProductServiceError::NotFound)
+                }
+            })
+    }
+
+    /// Matches however the SKU is cased or padded.
+    pub async fn find_by_sku(&self, sku: &str) -> Result<Product, ProductServiceError<R::Error>> {
+        let Some(sku) = sku::normalize(sku) else {
+            return Err(ProductServiceError::NotFound);
+        };
+        self.repo
+            .find_by_sku(&sku)
+            .await
+            .map_err(ProductServiceError::Repository)?
+            .ok_or(ProductServiceError::NotFound)
+    }
+
+    pub async fn price_history(
+        &self,
+        id: ProductId,
+    ) -> Result<Vec<PriceChange>, ProductServiceError<R::Error>> {
+        self.find(id, false).await?;
+        self.repo
+            .read_price_history(id)
+            .await
+            .map_err(ProductServiceError::Repository)
+    }
+
+    /// A product's SKU can be set once, when it has none; after that it can
+    /// only be repeated.
     pub async fn modify(
         &self,
-        id: Uuid,
-        name: String,
-        description: String,
-        price: u32,
+        id: ProductId,
+        product: NewProduct,
+        expected_version: Option<i32>,
+    ) -> Result<Product, ProductServiceError<R::Error>> {
+        let product = NewProduct {
+            name: validation::name(product.name).map_err(ProductServiceError::Validation)?,
+            tags: tags::normalize(product.tags),
+            sku: product.sku.map(validate_sku).transpose()?,
+            ..product
+        };
+        match self.repo.update(id, product, expected_version).await {
+            Ok(Some(product)) => Ok(product),
+            Ok(None) => Err(ProductServiceError::NotFound),
+            Err(error) if error.is_immutable() => Err(ProductServiceError::Validation(
+                InvalidField::new("sku", "sku can't be changed"),
+            )),
+            Err(error) => Err(ProductServiceError::Repository(error)),
+        }
+    }
+
+    /// Applies only the fields that are `Some`.
+    pub async fn modify_partial(
+        &self,
+        id: ProductId,
+        name: Option<String>,
+        description: Option<String>,
+        price: Option<Price>,
+        tags: Option<Vec<String>>,
+        expected_version: Option<i32>,
     ) -> Result<Product, ProductServiceError<R::Error>> {
+        let name = name
+            .map(validation::name)
+            .transpose()
+            .map_err(ProductServiceError::Validation)?;
+        let tags = tags.map(tags::normalize);
         self.repo
-            .update(id, name, description, price)
+            .patch(id, name, description, price, tags, expected_version)
             .await
             .map_err(ProductServiceError::Repository)
             .and_then(|opt| {
@@ -92,7 +427,7 @@ impl<R: ProductRepository> ProductService<R> {
             })
     }
 
-    pub async fn remove(&self, id: Uuid) -> Result<(), ProductServiceError<R::Error>> {
+    pub async fn remove(&self, id: ProductId) -> Result<(), ProductServiceError<R::Error>> {
         self.repo
             .delete(id)
             .await
@@ -105,176 +440,490 @@ impl<R: ProductRepository> ProductService<R> {
                 }
             })
     }
+
+    /// Returns how many of `ids` were actually deleted.
+    pub async fn remove_many(&self, ids: &[ProductId]) -> Result<u64, R::Error> {
+        self.repo.delete_many(ids).await
+    }
+
+    pub async fn assign_category(
+        &self,
+        id: ProductId,
+        category_id: Option<CategoryId>,
+    ) -> Result<Product, ProductServiceError<R::Error>> {
+        match self.repo.set_category(id, category_id).await {
+            Ok(Some(product)) => Ok(product),
+            Ok(None) => Err(ProductServiceError::NotFound),
+            Err(error) if error.is_conflict() => Err(ProductServiceError::Validation(
+                InvalidField::new("category_id", "category does not exist"),
+            )),
+            Err(error) => Err(ProductServiceError::Repository(error)),
+        }
+    }
+
+    pub async fn set_stock(
+        &self,
+        id: ProductId,
+        stock: u32,
+    ) -> Result<Product, ProductServiceError<R::Error>> {
+        self.repo
+            .set_stock(id, stock)
+            .await
+            .map_err(ProductServiceError::Repository)?
+            .ok_or(ProductServiceError::NotFound)
+    }
+
+    pub async fn reserve(
+        &self,
+        id: ProductId,
+        quantity: u32,
+    ) -> Result<Product, ProductServiceError<R::Error>> {
+        if quantity == 0 {
+            return Err(ProductServiceError::Validation(InvalidField::new(
+                "quantity",
+                "quantity must be at least 1",
+            )));
+        }
+        match self
+            .repo
+            .reserve(id, quantity)
+            .await
+            .map_err(ProductServiceError::Repository)?
+        {
+            Reservation::Reserved(product) => Ok(*product),
+            Reservation::Insufficient { available } => {
+                Err(ProductServiceError::InsufficientStock { available })
+            }
+            Reservation::NotFound => Err(ProductServiceError::NotFound),
+        }
+    }
+
+    pub async fn restore(&self, id: ProductId) -> Result<Product, ProductServiceError<R::Error>> {
+        self.repo
+            .restore(id)
+            .await
+            .map_err(ProductServiceError::Repository)
+            .and_then(|opt| {
+                if let Some(product) = opt {
+                    Ok(product)
+                } else {
+                    Err(ProductServiceError::NotFound)
+                }
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::application::validation::MAX_NAME_LENGTH;
+    use crate::repositories::memory_product_repository::{
+        InMemoryError, InMemoryProductRepository,
+    };
     use uuid::Uuid;
 
-    #[derive(Default)]
-    struct MockProductRepository {
-        products: std::sync::Mutex<Vec<Product>>,
-        fail: bool,
+    fn price(cents: u32) -> Price {
+        Price::new(cents).unwrap()
     }
 
-    #[derive(Debug)]
-    struct MockError;
-    impl std::fmt::Display for MockError {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "Mock repository error")
-        }
-    }
-    impl std::error::Error for MockError {}
-
-    impl ProductRepository for MockProductRepository {
-        type Error = MockError;
-
-        async fn create(
-            &self,
-            name: String,
-            description: String,
-            price: u32,
-        ) -> Result<Product, Self::Error> {
-            if self.fail {
-                return Err(MockError);
-            }
+    #[tokio::test]
+    async fn add_product_success() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
 
-            let product = Product {
-                id: Uuid::new_v4(),
-                name,
-                description,
-                price,
-            };
+        let product = service
+            .add(
+                "Book".into(),
+                "A nice book".into(),
+                price(1000),
+                Vec::new(),
+                None,
+            )
+            .await
+            .unwrap();
 
-            self.products.lock().unwrap().push(product.clone());
-            Ok(product)
-        }
+        assert_eq!(product.name, "Book");
+        assert_eq!(product.price, price(1000));
+    }
 
-        async fn read_all(&self) -> Result<Vec<Product>, Self::Error> {
-            if self.fail {
-                return Err(MockError);
-            }
+    #[tokio::test]
+    async fn tags_are_normalized_on_write() {
+        let service = ProductService::new(InMemoryProductRepository::default());
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
 
-            Ok(self.products.lock().unwrap().clone())
-        }
+        let product = service
+            .add(
+                "Book".into(),
+                "Desc".into(),
+                price(10),
+                tags(&[" sale", "new", "sale ", ""]),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(product.tags, ["sale", "new"]);
 
-        async fn read_one(&self, id: Uuid) -> Result<Option<Product>, Self::Error> {
-            if self.fail {
-                return Err(MockError);
-            }
+        let product = service
+            .modify_partial(product.id, None, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(product.tags, ["sale", "new"]);
 
-            Ok(self
-                .products
-                .lock()
-                .unwrap()
-                .iter()
-                .find(|p| p.id == id)
-                .cloned())
-        }
+        let product = service
+            .modify_partial(
+                product.id,
+                None,
+                None,
+                None,
+                Some(tags(&["new", " new"])),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(product.tags, ["new"]);
+    }
 
-        async fn update(
-            &self,
-            id: Uuid,
-            name: String,
-            description: String,
-            price: u32,
-        ) -> Result<Option<Product>, Self::Error> {
-            if self.fail {
-                return Err(MockError);
-            }
+    #[tokio::test]
+    async fn add_product_validates_name() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
 
-            let mut products = self.products.lock().unwrap();
-            if let Some(p) = products.iter_mut().find(|p| p.id == id) {
-                p.name = name;
-                p.description = description;
-                p.price = price;
-                return Ok(Some(p.clone()));
-            }
+        let product = service
+            .add(
+                "  Book  ".into(),
+                "Desc".into(),
+                price(10),
+                Vec::new(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(product.name, "Book");
 
-            Ok(None)
+        for name in [
+            "".to_owned(),
+            "   ".to_owned(),
+            "a".repeat(MAX_NAME_LENGTH + 1),
+        ] {
+            let result = service
+                .add(name, "Desc".into(), price(10), Vec::new(), None)
+                .await;
+            assert!(matches!(result, Err(ProductServiceError::Validation(_))));
         }
+        let padded = format!(" {} ", "a".repeat(MAX_NAME_LENGTH));
+        assert!(
+            service
+                .add(padded, "Desc".into(), price(10), Vec::new(), None)
+                .await
+                .is_ok()
+        );
+    }
 
-        async fn delete(&self, id: Uuid) -> Result<bool, Self::Error> {
-            if self.fail {
-                return Err(MockError);
-            }
+    #[tokio::test]
+    async fn modify_product_validates_name() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
 
-            let mut products = self.products.lock().unwrap();
-            let len_before = products.len();
-            products.retain(|p| p.id != id);
+        let product = service
+            .add("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
 
-            Ok(products.len() != len_before)
-        }
+        let result = service
+            .modify(
+                product.id,
+                NewProduct {
+                    name: " ".into(),
+                    description: "Desc".into(),
+                    price: price(10),
+                    tags: Vec::new(),
+                    sku: None,
+                },
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(ProductServiceError::Validation(_))));
+        let result = service
+            .modify(
+                product.id,
+                NewProduct {
+                    name: "a".repeat(MAX_NAME_LENGTH + 1),
+                    description: "Desc".into(),
+                    price: price(10),
+                    tags: Vec::new(),
+                    sku: None,
+                },
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(ProductServiceError::Validation(_))));
+
+        let renamed = service
+            .modify(
+                product.id,
+                NewProduct {
+                    name: " Novel ".into(),
+                    description: "Desc".into(),
+                    price: price(10),
+                    tags: Vec::new(),
+                    sku: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(renamed.name, "Novel");
     }
 
     #[tokio::test]
-    async fn add_product_success() {
-        let repo = MockProductRepository::default();
+    async fn modify_partial_keeps_omitted_fields() {
+        let repo = InMemoryProductRepository::default();
         let service = ProductService::new(repo);
 
         let product = service
-            .add("Book".into(), "A nice book".into(), 1000)
+            .add("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+        let patched = service
+            .modify_partial(product.id, None, None, Some(price(25)), None, None)
             .await
             .unwrap();
 
-        assert_eq!(product.name, "Book");
-        assert_eq!(product.price, 1000);
+        assert_eq!(patched.name, "Book");
+        assert_eq!(patched.description, "Desc");
+        assert_eq!(patched.price, price(25));
+        assert!(patched.updated_at >= product.updated_at);
+
+        let result = service
+            .modify_partial(product.id, Some(" ".into()), None, None, None, None)
+            .await;
+        assert!(matches!(result, Err(ProductServiceError::Validation(_))));
+
+        let missing = service
+            .modify_partial(
+                Uuid::new_v4().into(),
+                None,
+                None,
+                Some(price(1)),
+                None,
+                None,
+            )
+            .await;
+        assert!(matches!(missing, Err(ProductServiceError::NotFound)));
     }
 
     #[tokio::test]
     async fn list_products_returns_all() {
-        let repo = MockProductRepository::default();
+        let repo = InMemoryProductRepository::default();
         let service = ProductService::new(repo);
 
         service
-            .add("Item 1".into(), "Desc".into(), 10)
+            .add("Item 1".into(), "Desc".into(), price(10), Vec::new(), None)
             .await
             .unwrap();
         service
-            .add("Item 2".into(), "Desc".into(), 20)
+            .add("Item 2".into(), "Desc".into(), price(20), Vec::new(), None)
             .await
             .unwrap();
 
-        let products = service.list().await.unwrap();
-        assert_eq!(products.len(), 2);
+        let products = service
+            .list(
+                &ProductFilter::default(),
+                Sort::default(),
+                Page::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(products.items.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn list_products_pages() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
+
+        for i in 0..3 {
+            service
+                .add(
+                    format!("Item {}", i),
+                    "Desc".into(),
+                    price(10),
+                    Vec::new(),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+
+        let paged = service
+            .list(
+                &ProductFilter::default(),
+                Sort::default(),
+                Page::new(Some(2), Some(2)),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(paged.items.len(), 1);
+        assert_eq!(paged.total, 3);
     }
 
     #[tokio::test]
     async fn find_product_not_found() {
-        let repo = MockProductRepository::default();
+        let repo = InMemoryProductRepository::default();
         let service = ProductService::new(repo);
 
-        let result = service.find(Uuid::new_v4()).await;
+        let result = service.find(Uuid::new_v4().into(), false).await;
 
         assert!(matches!(result, Err(ProductServiceError::NotFound)));
     }
 
+    #[tokio::test]
+    async fn find_by_slug_suffixes_duplicate_names() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
+
+        service
+            .add("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+        let second = service
+            .add("Book".into(), "Desc".into(), price(20), Vec::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(second.slug, "book-2");
+
+        let found = service.find_by_slug("book-2").await.unwrap();
+        assert_eq!(found.id, second.id);
+
+        let missing = service.find_by_slug("book-3").await;
+        assert!(matches!(missing, Err(ProductServiceError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn list_recent_skips_stale_products() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
+
+        let stale = service
+            .add("Old".into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+        service
+            .add("New".into(), "Desc".into(), price(20), Vec::new(), None)
+            .await
+            .unwrap();
+        service.repo.put(Product {
+            updated_at: stale.updated_at - Duration::from_secs(2 * 60 * 60),
+            ..stale
+        });
+
+        let recent = service
+            .list_recent(Duration::from_secs(60 * 60))
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].name, "New");
+    }
+
+    #[tokio::test]
+    async fn price_history_records_price_changes_only() {
+        let repo = InMemoryProductRepository::default();
+        let service = ProductService::new(repo);
+
+        let product = service
+            .add("Book".into(), "Desc".into(), price(100), Vec::new(), None)
+            .await
+            .unwrap();
+        service
+            .modify(
+                product.id,
+                NewProduct {
+                    name: "Book".into(),
+                    description: "New desc".into(),
+                    price: price(100),
+                    tags: Vec::new(),
+                    sku: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        service
+            .modify(
+                product.id,
+                NewProduct {
+                    name: "Book".into(),
+                    description: "New desc".into(),
+                    price: price(150),
+                    tags: Vec::new(),
+                    sku: None,
+                },
+                None,
+            )
+            .await
+            .unwrap();
+
+        let history = service.price_history(product.id).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].old_price, price(100));
+        assert_eq!(history[0].new_price, price(150));
+
+        let missing = service.price_history(Uuid::new_v4().into()).await;
+        assert!(matches!(missing, Err(ProductServiceError::NotFound)));
+    }
+
     #[tokio::test]
     async fn repository_error_is_wrapped() {
-        let repo = MockProductRepository {
-            fail: true,
-            ..Default::default()
-        };
+        let repo = InMemoryProductRepository::default().with_failures(true);
         let service = ProductService::new(repo);
 
-        let result = service.list().await;
+        let result = service
+            .list(
+                &ProductFilter::default(),
+                Sort::default(),
+                Page::default(),
+                false,
+            )
+            .await;
 
-        assert!(matches!(result, Err(MockError)));
+        assert!(matches!(result, Err(InMemoryError::Injected)));
     }
 
     #[tokio::test]
     async fn remove_product_success() {
-        let repo = MockProductRepository::default();
+        let repo = InMemoryProductRepository::default();
         let service = ProductService::new(repo);
 
-        let product = service.add("Temp".into(), "Temp".into(), 1).await.unwrap();
-        let len_before = service.list().await.unwrap().len();
+        let product = service
+            .add("Temp".into(), "Temp".into(), price(1), Vec::new(), None)
+            .await
+            .unwrap();
+        let len_before = service
+            .list(
+                &ProductFilter::default(),
+                Sort::default(),
+                Page::default(),
+                false,
+            )
+            .await
+            .unwrap()
+            .total;
 
         let result = service.remove(product.id).await;
         assert!(result.is_ok());
 
-        let len_after = service.list().await.unwrap().len();
+        let len_after = service
+            .list(
+                &ProductFilter::default(),
+                Sort::default(),
+                Page::default(),
+                false,
+            )
+            .await
+            .unwrap()
+            .total;
         assert_ne!(len_before, len_after);
     }
 }
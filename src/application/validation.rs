@@ -0,0 +1,42 @@
+use std::{error::Error, fmt};
+
+/// Input a service rejected before it reached the repository, together with
+/// the request field it came from.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidField {
+    pub field: &'static str,
+    pub message: String,
+}
+impl InvalidField {
+    pub fn new(field: &'static str, message: impl ToString) -> Self {
+        Self {
+            field,
+            message: message.to_string(),
+        }
+    }
+}
+impl fmt::Display for InvalidField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl Error for InvalidField {}
+
+/// Longest product or category name accepted, in characters, after trimming.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Trims a product or category name and checks it's neither blank nor too
+/// long.
+pub fn name(name: String) -> Result<String, InvalidField> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(InvalidField::new("name", "name must not be empty"));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(InvalidField::new(
+            "name",
+            format!("name must be at most {} characters", MAX_NAME_LENGTH),
+        ));
+    }
+    Ok(name.to_owned())
+}
@@ -0,0 +1,338 @@
+use std::{
+    env::{self, VarError},
+    error::Error,
+    fmt,
+    str::FromStr,
+    time::Duration,
+};
+
+use actix_web::http::header::HeaderName;
+use sqlx::postgres::PgPoolOptions;
+
+use crate::{
+    domain::price_unit::PriceUnit,
+    handlers::{json::DEFAULT_JSON_LIMIT, product_handlers::Limits, query::QueryLimits},
+    middleware::{
+        auth::{AuthPolicy, AuthScope},
+        cors::{default_allowed_headers, parse_header_list},
+        https::{HttpsEnforcement, HttpsPolicy},
+    },
+    repositories::product_repository::PgProductRepository,
+};
+
+/// Every setting the server reads, documented in `.env.example`.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub database_url: String,
+    pub pool: PoolConfig,
+    /// Extra connection attempts at startup; the delay doubles each time, up
+    /// to a cap.
+    pub startup_db_retries: u32,
+    pub startup_db_retry_delay: Duration,
+    /// Apply pending migrations before serving.
+    pub run_migrations: bool,
+    /// `None` leaves statements without a time limit.
+    pub statement_timeout: Option<Duration>,
+    pub max_result_rows: u32,
+    /// Largest JSON request body accepted, in bytes.
+    pub max_json_body_bytes: usize,
+    pub query_limits: QueryLimits,
+    pub default_price_unit: PriceUnit,
+    pub regenerate_slugs: bool,
+    /// Bump `updated_at` when a product is restored.
+    pub touch_on_restore: bool,
+    pub cors_allowed_headers: Vec<HeaderName>,
+    pub https_policy: HttpsPolicy,
+    pub auth: AuthPolicy,
+}
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| env::var(name))
+    }
+
+    /// What the capabilities endpoint reports, so clients see the same
+    /// values the server enforces.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_result_rows: self.max_result_rows,
+            max_json_body_bytes: self.max_json_body_bytes,
+            query: self.query_limits,
+            default_price_unit: self.default_price_unit,
+        }
+    }
+
+    /// Reads each variable through `lookup`, reporting every missing or
+    /// invalid one at once rather than stopping at the first.
+    pub fn from_lookup(
+        lookup: impl Fn(&str) -> Result<String, VarError>,
+    ) -> Result<Self, ConfigError> {
+        let mut vars = Vars {
+            lookup,
+            problems: Vec::new(),
+        };
+
+        let config = Self {
+            host: vars.parse_or("HOST", || "127.0.0.1".to_owned()),
+            port: vars.parse_or("PORT", || 8080),
+            database_url: vars.required("DATABASE_URL"),
+            pool: PoolConfig {
+                max_connections: vars.parse_or("MAX_DB_CONNECTIONS", || 10),
+                min_connections: vars.parse_or("MIN_DB_CONNECTIONS", || 0),
+                acquire_timeout: vars
+                    .with("DB_ACQUIRE_TIMEOUT_MS", millis, || Duration::from_secs(30)),
+                idle_timeout: vars.with("DB_IDLE_TIMEOUT_SECS", seconds, || {
+                    Duration::from_secs(10 * 60)
+                }),
+            },
+            startup_db_retries: vars.parse_or("STARTUP_DB_RETRIES", || 5),
+            startup_db_retry_delay: vars
+                .with("STARTUP_DB_RETRY_DELAY", seconds, || Duration::from_secs(1)),
+            run_migrations: vars.parse_or("RUN_MIGRATIONS", || false),
+            statement_timeout: vars.with(
+                "DB_STATEMENT_TIMEOUT_MS",
+                |value| millis(value).map(Some),
+                || None,
+            ),
+            max_result_rows: vars
+                .parse_or("MAX_RESULT_ROWS", || PgProductRepository::DEFAULT_MAX_ROWS),
+            max_json_body_bytes: vars.parse_or("MAX_JSON_BODY_BYTES", || DEFAULT_JSON_LIMIT),
+            query_limits: QueryLimits {
+                max_params: vars.parse_or("MAX_QUERY_PARAMS", || QueryLimits::DEFAULT_MAX_PARAMS),
+                max_length: vars.parse_or("MAX_QUERY_LENGTH", || QueryLimits::DEFAULT_MAX_LENGTH),
+            },
+            default_price_unit: vars.parse_or("DEFAULT_PRICE_UNIT", PriceUnit::default),
+            regenerate_slugs: vars.parse_or("SLUG_REGENERATE_ON_RENAME", || false),
+            touch_on_restore: vars.parse_or("RESTORE_TOUCHES_UPDATED_AT", || true),
+            cors_allowed_headers: vars.with(
+                "CORS_ALLOWED_HEADERS",
+                parse_header_list,
+                default_allowed_headers,
+            ),
+            https_policy: HttpsPolicy {
+                enforcement: vars.parse_or("ENFORCE_HTTPS", || HttpsEnforcement::Off),
+                trust_forwarded_proto: vars.parse_or("TRUST_PROXY_HEADERS", || false),
+            },
+            auth: AuthPolicy {
+                scope: vars.parse_or("REQUIRE_AUTH", || AuthScope::Off),
+                jwt_secret: vars.with("JWT_SECRET", |value| value.parse().map(Some), || None),
+            },
+        };
+
+        if config.pool.min_connections > config.pool.max_connections {
+            vars.problems.push((
+                "MIN_DB_CONNECTIONS",
+                VarProblem::Invalid("must not exceed MAX_DB_CONNECTIONS".into()),
+            ));
+        }
+
+        let has_secret = config
+            .auth
+            .jwt_secret
+            .as_ref()
+            .is_some_and(|secret| !secret.is_empty());
+        if config.auth.scope != AuthScope::Off && !has_secret {
+            vars.problems.push(("JWT_SECRET", VarProblem::Missing));
+        }
+
+        if vars.problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError {
+                problems: vars.problems,
+            })
+        }
+    }
+}
+
+/// Sizing and timeouts for the Postgres connection pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    /// Connections kept open even when idle, so a burst doesn't pay for
+    /// connecting.
+    pub min_connections: u32,
+    /// How long a request waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// Idle connections above `min_connections` are closed after this long.
+    pub idle_timeout: Duration,
+}
+impl PoolConfig {
+    pub fn options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+    }
+}
+
+fn seconds(value: &str) -> Result<Duration, std::num::ParseIntError> {
+    value.parse().map(Duration::from_secs)
+}
+
+fn millis(value: &str) -> Result<Duration, std::num::ParseIntError> {
+    value.parse().map(Duration::from_millis)
+}
+
+struct Vars<F> {
+    lookup: F,
+    problems: Vec<(&'static str, VarProblem)>,
+}
+impl<F: Fn(&str) -> Result<String, VarError>> Vars<F> {
+    /// Parses `name` if set, falling back to `default` when it's unset. An
+    /// invalid value is recorded and also falls back, so parsing can go on.
+    fn with<T, E: fmt::Display>(
+        &mut self,
+        name: &'static str,
+        parse: impl FnOnce(&str) -> Result<T, E>,
+        default: impl FnOnce() -> T,
+    ) -> T {
+        let problem = match (self.lookup)(name) {
+            Err(VarError::NotPresent) => return default(),
+            Err(error) => VarProblem::Invalid(error.to_string()),
+            Ok(value) => match parse(&value) {
+                Ok(value) => return value,
+                Err(error) => VarProblem::Invalid(error.to_string()),
+            },
+        };
+        self.problems.push((name, problem));
+        default()
+    }
+
+    fn parse_or<T: FromStr>(&mut self, name: &'static str, default: impl FnOnce() -> T) -> T
+    where
+        T::Err: fmt::Display,
+    {
+        self.with(name, str::parse, default)
+    }
+
+    fn required(&mut self, name: &'static str) -> String {
+        let value = self.parse_or(name, String::new);
+        if value.is_empty() && !self.problems.iter().any(|(problem, _)| *problem == name) {
+            self.problems.push((name, VarProblem::Missing));
+        }
+        value
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VarProblem {
+    Missing,
+    Invalid(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    pub problems: Vec<(&'static str, VarProblem)>,
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration")?;
+        for (i, (name, problem)) in self.problems.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            match problem {
+                VarProblem::Missing => write!(f, "{}{} is not set", separator, name)?,
+                VarProblem::Invalid(error) => {
+                    write!(f, "{}{} is invalid ({})", separator, name, error)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_lookup(|name| vars.get(name).cloned().ok_or(VarError::NotPresent))
+    }
+
+    #[test]
+    fn fills_in_defaults() {
+        let config = load(&[("DATABASE_URL", "postgres://localhost/db")]).unwrap();
+
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.database_url, "postgres://localhost/db");
+        assert_eq!(config.startup_db_retry_delay, Duration::from_secs(1));
+        assert_eq!(config.statement_timeout, None);
+        assert!(!config.run_migrations);
+        assert_eq!(config.https_policy.enforcement, HttpsEnforcement::Off);
+    }
+
+    #[test]
+    fn reads_overrides() {
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/db"),
+            ("PORT", "9000"),
+            ("DB_ACQUIRE_TIMEOUT_MS", "500"),
+            ("DB_STATEMENT_TIMEOUT_MS", "250"),
+            ("DEFAULT_PRICE_UNIT", "major"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.pool.acquire_timeout, Duration::from_millis(500));
+        assert_eq!(config.statement_timeout, Some(Duration::from_millis(250)));
+        assert_eq!(config.default_price_unit, PriceUnit::Major);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let Err(error) = load(&[("PORT", "http"), ("ENFORCE_HTTPS", "always")]) else {
+            panic!("invalid configuration was accepted");
+        };
+
+        let names: Vec<_> = error.problems.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["PORT", "DATABASE_URL", "ENFORCE_HTTPS"]);
+        assert_eq!(error.problems[1].1, VarProblem::Missing);
+    }
+
+    #[test]
+    fn rejects_more_idle_than_total_connections() {
+        let Err(error) = load(&[
+            ("DATABASE_URL", "postgres://localhost/db"),
+            ("MAX_DB_CONNECTIONS", "5"),
+            ("MIN_DB_CONNECTIONS", "6"),
+        ]) else {
+            panic!("invalid pool size was accepted");
+        };
+
+        assert_eq!(error.problems.len(), 1);
+        assert_eq!(error.problems[0].0, "MIN_DB_CONNECTIONS");
+    }
+
+    #[test]
+    fn requires_a_secret_when_auth_is_on() {
+        let config = load(&[("DATABASE_URL", "postgres://localhost/db")]).unwrap();
+        assert_eq!(config.auth.scope, AuthScope::Off);
+
+        let Err(error) = load(&[
+            ("DATABASE_URL", "postgres://localhost/db"),
+            ("REQUIRE_AUTH", "writes"),
+        ]) else {
+            panic!("auth without a secret was accepted");
+        };
+        assert_eq!(error.problems, [("JWT_SECRET", VarProblem::Missing)]);
+
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/db"),
+            ("REQUIRE_AUTH", "all"),
+            ("JWT_SECRET", "s3cret"),
+        ])
+        .unwrap();
+        assert_eq!(config.auth.scope, AuthScope::All);
+        assert!(!format!("{:?}", config).contains("s3cret"));
+    }
+}
@@ -0,0 +1,36 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Keeps category ids from being mixed up with product ids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct CategoryId(Uuid);
+impl From<Uuid> for CategoryId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+impl fmt::Display for CategoryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl FromStr for CategoryId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+/// A group of products. Names are unique, ignoring case.
+#[derive(Clone)]
+pub struct Category {
+    pub id: CategoryId,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
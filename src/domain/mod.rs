@@ -1 +1,8 @@
+pub mod category;
+pub mod price;
+pub mod price_unit;
 pub mod product;
+pub mod sku;
+pub mod slug;
+pub mod tags;
+pub mod tax_rate;
@@ -0,0 +1,81 @@
+use std::{error::Error, fmt};
+
+use serde::Serialize;
+
+/// A price in cents, guaranteed to fit the `INTEGER` column it's stored in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct Price(u32);
+impl Price {
+    pub const MAX_CENTS: u32 = i32::MAX as u32;
+
+    pub fn new(cents: u32) -> Result<Self, PriceError> {
+        if cents > Self::MAX_CENTS {
+            return Err(PriceError::TooLarge);
+        }
+        Ok(Self(cents))
+    }
+
+    pub fn cents(&self) -> u32 {
+        self.0
+    }
+}
+impl TryFrom<i32> for Price {
+    type Error = PriceError;
+
+    /// Rejects negative values instead of wrapping them around, so a bad row
+    /// surfaces as an error rather than a huge price.
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        u32::try_from(value)
+            .map_err(|_| PriceError::Negative)
+            .and_then(Self::new)
+    }
+}
+impl From<Price> for i32 {
+    fn from(value: Price) -> Self {
+        // `Price::new` keeps the value within `i32::MAX`.
+        value.0 as i32
+    }
+}
+impl From<Price> for i64 {
+    fn from(value: Price) -> Self {
+        value.0.into()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PriceError {
+    Negative,
+    TooLarge,
+}
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Negative => write!(f, "price must not be negative"),
+            Self::TooLarge => write!(f, "price must be at most {} cents", Price::MAX_CENTS),
+        }
+    }
+}
+impl Error for PriceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enforces_bounds() {
+        assert_eq!(Price::new(0).map(|p| p.cents()), Ok(0));
+        assert_eq!(
+            Price::new(Price::MAX_CENTS).map(|p| p.cents()),
+            Ok(Price::MAX_CENTS)
+        );
+        assert_eq!(Price::new(Price::MAX_CENTS + 1), Err(PriceError::TooLarge));
+    }
+
+    #[test]
+    fn rejects_negative_database_values() {
+        assert_eq!(Price::try_from(1299).map(|p| p.cents()), Ok(1299));
+        assert_eq!(Price::try_from(-1), Err(PriceError::Negative));
+        assert_eq!(i32::from(Price::try_from(i32::MAX).unwrap()), i32::MAX);
+    }
+}
@@ -0,0 +1,151 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::domain::price::Price;
+
+/// The unit a client wrote a price in. Prices are always stored in cents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PriceUnit {
+    #[default]
+    Cents,
+    /// Whole currency units, e.g. `12.99` for 1299 cents.
+    Major,
+}
+impl PriceUnit {
+    pub const ALL: [Self; 2] = [Self::Cents, Self::Major];
+    const MAJOR_DECIMALS: usize = 2;
+
+    /// The name clients write in `price_unit`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cents => "cents",
+            Self::Major => "major",
+        }
+    }
+
+    fn decimals(&self) -> usize {
+        match self {
+            Self::Cents => 0,
+            Self::Major => Self::MAJOR_DECIMALS,
+        }
+    }
+
+    /// Converts a decimal string in this unit to cents exactly, without going
+    /// through `f64`. More decimal places than the unit allows is an error
+    /// rather than a silent rounding.
+    pub fn to_cents(&self, amount: &str) -> Result<Price, InvalidPrice> {
+        let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(InvalidPrice::Malformed);
+        }
+
+        // Trailing zeros (as in `100.0`) don't add precision.
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > self.decimals() {
+            return Err(InvalidPrice::TooPrecise {
+                decimals: self.decimals(),
+            });
+        }
+
+        let scale = 10u32.pow(self.decimals() as u32);
+        let whole: u32 = whole.parse().map_err(|_| InvalidPrice::OutOfRange)?;
+        let fraction: u32 = match fraction {
+            "" => 0,
+            fraction => format!("{:0<width$}", fraction, width = self.decimals())
+                .parse()
+                .map_err(|_| InvalidPrice::Malformed)?,
+        };
+
+        whole
+            .checked_mul(scale)
+            .and_then(|whole| whole.checked_add(fraction))
+            .and_then(|cents| Price::new(cents).ok())
+            .ok_or(InvalidPrice::OutOfRange)
+    }
+}
+impl FromStr for PriceUnit {
+    type Err = InvalidPriceUnit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cents" => Ok(Self::Cents),
+            "major" => Ok(Self::Major),
+            _ => Err(InvalidPriceUnit),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidPriceUnit;
+impl fmt::Display for InvalidPriceUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "price unit must be 'cents' or 'major'")
+    }
+}
+impl Error for InvalidPriceUnit {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvalidPrice {
+    Malformed,
+    TooPrecise { decimals: usize },
+    OutOfRange,
+}
+impl fmt::Display for InvalidPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "price must be a non-negative decimal number"),
+            Self::TooPrecise { decimals } => {
+                write!(f, "price must have at most {} decimal places", decimals)
+            }
+            Self::OutOfRange => write!(f, "price is too large"),
+        }
+    }
+}
+impl Error for InvalidPrice {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cents(unit: PriceUnit, amount: &str) -> Result<u32, InvalidPrice> {
+        unit.to_cents(amount).map(|price| price.cents())
+    }
+
+    #[test]
+    fn converts_both_units_to_cents() {
+        assert_eq!(cents(PriceUnit::Cents, "1299"), Ok(1299));
+        assert_eq!(cents(PriceUnit::Cents, "100.0"), Ok(100));
+        assert_eq!(cents(PriceUnit::Major, "12.99"), Ok(1299));
+        assert_eq!(cents(PriceUnit::Major, "12.9"), Ok(1290));
+        assert_eq!(cents(PriceUnit::Major, "12"), Ok(1200));
+        assert_eq!(cents(PriceUnit::Major, "0.07"), Ok(7));
+    }
+
+    #[test]
+    fn rejects_over_precise_prices() {
+        assert_eq!(
+            PriceUnit::Major.to_cents("12.999"),
+            Err(InvalidPrice::TooPrecise { decimals: 2 })
+        );
+        assert_eq!(
+            PriceUnit::Cents.to_cents("12.5"),
+            Err(InvalidPrice::TooPrecise { decimals: 0 })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_and_out_of_range_prices() {
+        for malformed in ["", ".5", "-1", "1e3", "abc"] {
+            assert_eq!(
+                PriceUnit::Major.to_cents(malformed),
+                Err(InvalidPrice::Malformed),
+                "{}",
+                malformed
+            );
+        }
+        assert_eq!(
+            PriceUnit::Major.to_cents("21474836.48"),
+            Err(InvalidPrice::OutOfRange)
+        );
+    }
+}
@@ -1,9 +1,158 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::domain::{category::CategoryId, price::Price, tax_rate::TaxRate};
+
+/// Keeps product ids from being mixed up with other kinds of ids.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct ProductId(Uuid);
+impl From<Uuid> for ProductId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+impl fmt::Display for ProductId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl FromStr for ProductId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
 #[derive(Clone)]
 pub struct Product {
-    pub id: Uuid,
+    pub id: ProductId,
+    pub name: String,
+    pub slug: String,
+    /// Stock-keeping unit, trimmed and uppercased; see `sku::normalize`.
+    /// Fixed once the product is created.
+    pub sku: Option<String>,
+    pub description: String,
+    pub price: Price,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Starts at 1 and goes up with every change, for optimistic locking.
+    pub version: i32,
+    /// Set when the product was soft-deleted; repositories hide such
+    /// products from reads until they're restored.
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub category_id: Option<CategoryId>,
+    /// Trimmed and free of duplicates; see `tags::normalize`.
+    pub tags: Vec<String>,
+    /// Units available to reserve.
+    pub stock: u32,
+}
+/// Aggregates over the products that aren't soft-deleted. Without any, the
+/// prices are `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProductStats {
+    pub count: u64,
+    pub min_price: Option<Price>,
+    pub max_price: Option<Price>,
+    /// Rounded to the nearest cent.
+    pub average_price: Option<Price>,
+}
+/// The fields a create or full update sets; everything else starts at its
+/// default or is kept.
+#[derive(Clone, Debug)]
+pub struct NewProduct {
     pub name: String,
     pub description: String,
-    pub price: u32,
+    pub price: Price,
+    pub tags: Vec<String>,
+    pub sku: Option<String>,
+}
+#[derive(Clone)]
+pub struct PriceChange {
+    pub old_price: Price,
+    pub new_price: Price,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl Product {
+    /// Gross price in cents, saturating at `u32::MAX`.
+    pub fn price_with_tax(&self, rate: TaxRate) -> u32 {
+        let cents = self.price.cents();
+        let gross = cents as u64 + rate.tax_on(cents);
+        u32::try_from(gross).unwrap_or(u32::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(price: u32) -> Product {
+        let now = Utc::now();
+        Product {
+            id: Uuid::new_v4().into(),
+            name: "Book".into(),
+            slug: "book".into(),
+            sku: None,
+            description: "A nice book".into(),
+            price: Price::new(price).unwrap(),
+            created_at: now,
+            updated_at: now,
+            version: 1,
+            deleted_at: None,
+            category_id: None,
+            tags: Vec::new(),
+            stock: 0,
+        }
+    }
+
+    fn rate(s: &str) -> TaxRate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn product_id_round_trips_as_a_plain_uuid() {
+        let uuid = Uuid::new_v4();
+        let id = ProductId::from(uuid);
+
+        assert_eq!(id.to_string(), uuid.to_string());
+        assert_eq!(uuid.to_string().parse::<ProductId>().unwrap(), id);
+        assert_eq!(
+            serde_json::to_value(id).unwrap(),
+            serde_json::json!(uuid.to_string())
+        );
+    }
+
+    #[test]
+    fn price_with_tax_exact() {
+        assert_eq!(product(1000).price_with_tax(rate("0.2")), 1200);
+        assert_eq!(product(1000).price_with_tax(rate("0")), 1000);
+        assert_eq!(product(1000).price_with_tax(rate("1")), 2000);
+    }
+
+    #[test]
+    fn price_with_tax_rounds_half_up() {
+        // 10 * 0.05 = 0.5 cents
+        assert_eq!(product(10).price_with_tax(rate("0.05")), 11);
+        // 999 * 0.0825 = 82.4175 cents
+        assert_eq!(product(999).price_with_tax(rate("0.0825")), 1081);
+        // 1 * 0.0049 = 0.0049 cents
+        assert_eq!(product(1).price_with_tax(rate("0.0049")), 1);
+    }
+
+    #[test]
+    fn price_with_tax_fits_the_largest_price() {
+        // A full 100% tax on the largest price still fits in a `u32`.
+        assert_eq!(
+            product(Price::MAX_CENTS).price_with_tax(rate("1")),
+            u32::MAX - 1
+        );
+    }
 }
@@ -0,0 +1,16 @@
+/// SKUs are compared after trimming and uppercasing, so ` ab-1 ` and `AB-1`
+/// name the same product. `None` if nothing is left.
+pub fn normalize(sku: &str) -> Option<String> {
+    Some(sku.trim().to_uppercase()).filter(|sku| !sku.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_and_uppercases() {
+        assert_eq!(normalize(" ab-1\t").as_deref(), Some("AB-1"));
+        assert_eq!(normalize("   "), None);
+    }
+}
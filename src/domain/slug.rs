@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+
+const FALLBACK_SLUG: &str = "product";
+
+/// Lowercases ASCII letters and digits, turns whitespace, `-` and `_` runs into
+/// a single dash and drops everything else.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_')
+            && !slug.is_empty()
+            && !slug.ends_with('-')
+        {
+            slug.push('-');
+        }
+    }
+
+    match slug.trim_end_matches('-') {
+        "" => FALLBACK_SLUG.to_owned(),
+        slug => slug.to_owned(),
+    }
+}
+
+/// Returns `base` if it's free, otherwise `base-N` with the smallest free
+/// `N >= 2`.
+pub fn with_unique_suffix<'a>(base: &str, taken: impl IntoIterator<Item = &'a str>) -> String {
+    let taken: HashSet<&str> = taken.into_iter().collect();
+    if !taken.contains(base) {
+        return base.to_owned();
+    }
+
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|candidate| !taken.contains(candidate.as_str()))
+        .expect("there is always a free suffix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_normalizes_names() {
+        assert_eq!(slugify("A Nice Book"), "a-nice-book");
+        assert_eq!(slugify("  Rock & Roll  "), "rock-roll");
+        assert_eq!(slugify("C++ Primer, 5th_Edition"), "c-primer-5th-edition");
+        assert_eq!(slugify("Café Crème"), "caf-crme");
+        assert_eq!(slugify("--multiple---dashes--"), "multiple-dashes");
+    }
+
+    #[test]
+    fn slugify_falls_back_when_nothing_is_left() {
+        assert_eq!(slugify(""), "product");
+        assert_eq!(slugify("!!!"), "product");
+    }
+
+    #[test]
+    fn suffix_is_added_only_on_collision() {
+        assert_eq!(with_unique_suffix("book", []), "book");
+        assert_eq!(with_unique_suffix("book", ["book-2"]), "book");
+        assert_eq!(with_unique_suffix("book", ["book"]), "book-2");
+        assert_eq!(
+            with_unique_suffix("book", ["book", "book-2", "book-4"]),
+            "book-3"
+        );
+    }
+}
@@ -0,0 +1,24 @@
+use std::collections::HashSet;
+
+/// Trims each tag and drops blank and repeated ones, keeping the first
+/// occurrence's position. Tags are case-sensitive.
+pub fn normalize(tags: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trims_and_deduplicates() {
+        let tags = ["sale", " new ", "", "sale", "new", "  ", "Sale"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(normalize(tags), ["sale", "new", "Sale"]);
+    }
+}
@@ -0,0 +1,93 @@
+use std::{error::Error, fmt, str::FromStr};
+
+/// A tax rate between 0 and 1 stored in basis points (1/10000), so that tax
+/// can be computed on integer cents without floating-point drift.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaxRate {
+    basis_points: u32,
+}
+impl TaxRate {
+    pub const SCALE: u32 = 10_000;
+    const SCALE_DIGITS: usize = 4;
+
+    pub fn from_basis_points(basis_points: u32) -> Result<Self, InvalidTaxRate> {
+        if basis_points > Self::SCALE {
+            return Err(InvalidTaxRate);
+        }
+        Ok(Self { basis_points })
+    }
+
+    pub fn basis_points(&self) -> u32 {
+        self.basis_points
+    }
+
+    /// Tax owed on `cents`, rounded half up to the nearest cent.
+    pub fn tax_on(&self, cents: u32) -> u64 {
+        let scale = Self::SCALE as u64;
+        (cents as u64 * self.basis_points as u64 + scale / 2) / scale
+    }
+}
+impl FromStr for TaxRate {
+    type Err = InvalidTaxRate;
+
+    /// Parses decimal strings such as `0.2` or `0.0825` exactly, without going
+    /// through `f64`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty()
+            || !is_digits(whole)
+            || !is_digits(fraction)
+            || fraction.len() > Self::SCALE_DIGITS
+        {
+            return Err(InvalidTaxRate);
+        }
+
+        let whole: u32 = whole.parse().map_err(|_| InvalidTaxRate)?;
+        let fraction: u32 = format!("{:0<width$}", fraction, width = Self::SCALE_DIGITS)
+            .parse()
+            .map_err(|_| InvalidTaxRate)?;
+
+        whole
+            .checked_mul(Self::SCALE)
+            .and_then(|whole| whole.checked_add(fraction))
+            .ok_or(InvalidTaxRate)
+            .and_then(Self::from_basis_points)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidTaxRate;
+impl fmt::Display for InvalidTaxRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tax rate must be a decimal between 0 and 1 with at most 4 decimal places"
+        )
+    }
+}
+impl Error for InvalidTaxRate {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimal_strings() {
+        assert_eq!("0.2".parse::<TaxRate>().unwrap().basis_points(), 2000);
+        assert_eq!("0.0825".parse::<TaxRate>().unwrap().basis_points(), 825);
+        assert_eq!("1.0".parse::<TaxRate>().unwrap().basis_points(), 10_000);
+    }
+
+    #[test]
+    fn rejects_invalid_rates() {
+        for invalid in ["", ".2", "0.00001", "1.5", "-0.2", "abc", "0.2%"] {
+            assert_eq!(
+                invalid.parse::<TaxRate>(),
+                Err(InvalidTaxRate),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use actix_web::{HttpResponse, ResponseError, http::StatusCode};
+use serde::Serialize;
+
+use crate::{
+    application::{
+        category_service::CategoryServiceError,
+        product_service::{ClassifyError, ProductServiceError},
+        validation::InvalidField,
+    },
+    middleware::request_id::RequestId,
+};
+
+/// JSON error body: `{ "error": "...", "field": "..." }`, with `field` only
+/// present when the error is about a specific input.
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+}
+impl ApiError {
+    pub fn new(status: StatusCode, error: impl ToString) -> Self {
+        Self {
+            status,
+            error: error.to_string(),
+            field: None,
+        }
+    }
+
+    pub fn bad_request(error: impl ToString) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, error)
+    }
+
+    /// A well-formed body whose `field` fails validation.
+    pub fn invalid_field(field: &str, error: impl ToString) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, error).with_field(field)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not found")
+    }
+
+    /// Details stay in the logs; clients only learn that it wasn't their fault.
+    pub fn internal() -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+    }
+
+    pub fn with_field(mut self, field: &str) -> Self {
+        self.field = Some(field.to_owned());
+        self
+    }
+}
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{} at '{}'", self.error, field),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+impl From<&InvalidField> for ApiError {
+    fn from(invalid: &InvalidField) -> Self {
+        Self::invalid_field(invalid.field, &invalid.message)
+    }
+}
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(self)
+    }
+}
+
+/// Status for a repository error a service gave no more specific meaning.
+/// Timeouts are the database shedding load, so they get a 503 the client can
+/// retry instead of a 500.
+fn repository_status(error: &impl ClassifyError) -> StatusCode {
+    if error.is_timeout() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+/// Logs `context`, the service error wrapping `error`, and answers with
+/// [`repository_status`]; details stay in the logs.
+fn repository_error_response(
+    error: &impl ClassifyError,
+    context: &dyn fmt::Display,
+) -> HttpResponse {
+    if error.is_timeout() {
+        log::warn!("request {}: {}", RequestId::current(), context);
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "database timed out").error_response()
+    } else {
+        log::error!("request {}: {}", RequestId::current(), context);
+        ApiError::internal().error_response()
+    }
+}
+
+/// Lets handlers `?` service errors. Conflicts are the client's to resolve,
+/// so they get a 409, or a 412 when an `If-Match` version went stale.
+impl<E: ClassifyError + 'static> ResponseError for ProductServiceError<E> {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InsufficientStock { .. } => StatusCode::CONFLICT,
+            Self::Repository(error) if error.is_timeout() => repository_status(error),
+            Self::Repository(error) if error.is_conflict() => StatusCode::CONFLICT,
+            Self::Repository(error) if error.is_stale() => StatusCode::PRECONDITION_FAILED,
+            Self::Repository(error) => repository_status(error),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::NotFound => ApiError::not_found().error_response(),
+            Self::Validation(invalid) => ApiError::from(invalid).error_response(),
+            Self::InsufficientStock { .. } => {
+                ApiError::new(StatusCode::CONFLICT, self).error_response()
+            }
+            Self::Repository(error) if error.is_timeout() => repository_error_response(error, self),
+            Self::Repository(error) if error.is_conflict() => {
+                ApiError::new(StatusCode::CONFLICT, "conflicts with an existing product")
+                    .error_response()
+            }
+            Self::Repository(error) if error.is_stale() => ApiError::new(
+                StatusCode::PRECONDITION_FAILED,
+                "product was changed since it was read",
+            )
+            .error_response(),
+            Self::Repository(error) => repository_error_response(error, self),
+        }
+    }
+}
+
+/// Both kinds of conflict are the client's to resolve, so they get a 409.
+impl<E: ClassifyError + 'static> ResponseError for CategoryServiceError<E> {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::DuplicateName | Self::InUse => StatusCode::CONFLICT,
+            Self::Repository(error) => repository_status(error),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::NotFound => ApiError::not_found().error_response(),
+            Self::Validation(invalid) => ApiError::from(invalid).error_response(),
+            Self::DuplicateName => ApiError::new(
+                StatusCode::CONFLICT,
+                "a category with that name already exists",
+            )
+            .error_response(),
+            Self::InUse => {
+                ApiError::new(StatusCode::CONFLICT, "category still has products").error_response()
+            }
+            Self::Repository(error) => repository_error_response(error, self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockError {
+        timeout: bool,
+        conflict: bool,
+        stale: bool,
+    }
+    impl fmt::Display for MockError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "Mock repository error")
+        }
+    }
+    impl std::error::Error for MockError {}
+    impl ClassifyError for MockError {
+        fn is_timeout(&self) -> bool {
+            self.timeout
+        }
+
+        fn is_conflict(&self) -> bool {
+            self.conflict
+        }
+
+        fn is_stale(&self) -> bool {
+            self.stale
+        }
+    }
+
+    #[test]
+    fn service_errors_map_to_status_codes() {
+        let cases = [
+            (ProductServiceError::NotFound, StatusCode::NOT_FOUND),
+            (
+                ProductServiceError::Validation(InvalidField::new(
+                    "name",
+                    "name must not be empty",
+                )),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                ProductServiceError::InsufficientStock { available: 2 },
+                StatusCode::CONFLICT,
+            ),
+            (
+                ProductServiceError::Repository(MockError {
+                    timeout: false,
+                    conflict: false,
+                    stale: false,
+                }),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                ProductServiceError::Repository(MockError {
+                    timeout: true,
+                    conflict: false,
+                    stale: false,
+                }),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (
+                ProductServiceError::Repository(MockError {
+                    timeout: false,
+                    conflict: true,
+                    stale: false,
+                }),
+                StatusCode::CONFLICT,
+            ),
+            (
+                ProductServiceError::Repository(MockError {
+                    timeout: false,
+                    conflict: false,
+                    stale: true,
+                }),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+        ];
+
+        for (error, status) in cases {
+            assert_eq!(error.status_code(), status);
+            assert_eq!(error.error_response().status(), status);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::{
+    convert::Infallible,
+    future::{Ready, ready},
+};
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, http::header::ACCEPT};
+
+pub const V2_MEDIA_TYPE: &str = "application/vnd.product.v2+json";
+
+/// Response shape negotiated through the `Accept` header. Requests that don't
+/// ask for a versioned media type get `V1`, so existing clients keep working.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+impl FromRequest for ApiVersion {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let wants_v2 = req
+            .headers()
+            .get_all(ACCEPT)
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|media_type| media_type.split(';').next().map(str::trim) == Some(V2_MEDIA_TYPE));
+
+        ready(Ok(if wants_v2 {
+            ApiVersion::V2
+        } else {
+            ApiVersion::V1
+        }))
+    }
+}
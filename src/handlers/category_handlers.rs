@@ -0,0 +1,90 @@
+use actix_web::{HttpResponse, http::header::LOCATION, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    application::category_service::{CategoryRepository, CategoryService, CategoryServiceError},
+    domain::category::{Category, CategoryId},
+    handlers::json::ValidatedJson,
+    middleware::auth::AdminUser,
+};
+
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryDTO {
+    #[validate(length(min = 1, max = 255, message = "name must be 1 to 255 characters"))]
+    pub name: String,
+}
+#[derive(Serialize)]
+pub struct OutputCategoryDTO {
+    id: CategoryId,
+    name: String,
+    created_at: DateTime<Utc>,
+}
+impl From<Category> for OutputCategoryDTO {
+    fn from(value: Category) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            created_at: value.created_at,
+        }
+    }
+}
+
+pub async fn list_categories<R: CategoryRepository>(
+    service: web::Data<CategoryService<R>>,
+) -> Result<HttpResponse, CategoryServiceError<R::Error>> {
+    let categories = service
+        .list()
+        .await
+        .map_err(CategoryServiceError::Repository)?;
+    Ok(HttpResponse::Ok().json(
+        categories
+            .into_iter()
+            .map(OutputCategoryDTO::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+pub async fn add_category<R: CategoryRepository>(
+    _admin: AdminUser,
+    service: web::Data<CategoryService<R>>,
+    payload: ValidatedJson<CategoryDTO>,
+) -> Result<HttpResponse, CategoryServiceError<R::Error>> {
+    let category = service.add(payload.into_inner().name).await?;
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, format!("/api/categories/{}", category.id)))
+        .json(OutputCategoryDTO::from(category)))
+}
+
+pub async fn find_category<R: CategoryRepository>(
+    service: web::Data<CategoryService<R>>,
+    id: web::Path<CategoryId>,
+) -> Result<HttpResponse, CategoryServiceError<R::Error>> {
+    let category = service.find(id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(OutputCategoryDTO::from(category)))
+}
+
+pub async fn put_category<R: CategoryRepository>(
+    _admin: AdminUser,
+    service: web::Data<CategoryService<R>>,
+    id: web::Path<CategoryId>,
+    payload: ValidatedJson<CategoryDTO>,
+) -> Result<HttpResponse, CategoryServiceError<R::Error>> {
+    let category = service
+        .rename(id.into_inner(), payload.into_inner().name)
+        .await?;
+    Ok(HttpResponse::Ok().json(OutputCategoryDTO::from(category)))
+}
+
+/// Answers 409 while live products still belong to the category; move them
+/// out first.
+pub async fn remove_category<R: CategoryRepository>(
+    _admin: AdminUser,
+    service: web::Data<CategoryService<R>>,
+    id: web::Path<CategoryId>,
+) -> Result<HttpResponse, CategoryServiceError<R::Error>> {
+    service.remove(id.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
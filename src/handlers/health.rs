@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use actix_web::{HttpResponse, rt::time::timeout, web};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::middleware::request_id::RequestId;
+
+/// How long a probe waits on a dependency before reporting it down, so the
+/// load balancer's own timeout never fires first.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Down,
+}
+
+#[derive(Serialize)]
+pub struct HealthDTO {
+    db: Status,
+}
+
+async fn check<T>(name: &str, probe: impl Future<Output = Result<T, sqlx::Error>>) -> Status {
+    match timeout(CHECK_TIMEOUT, probe).await {
+        Ok(Ok(_)) => Status::Ok,
+        Ok(Err(error)) => {
+            log::warn!(
+                "request {}: {}: database unreachable: {}",
+                RequestId::current(),
+                name,
+                error
+            );
+            Status::Down
+        }
+        Err(_) => {
+            log::warn!(
+                "request {}: {}: database timed out after {:?}",
+                RequestId::current(),
+                name,
+                CHECK_TIMEOUT
+            );
+            Status::Down
+        }
+    }
+}
+
+fn report(db: Status) -> HttpResponse {
+    let report = HealthDTO { db };
+    if report.db == Status::Ok {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// 200 when every dependency answers, 503 otherwise; the body says which.
+pub async fn health(pool: web::Data<PgPool>) -> HttpResponse {
+    let ping = sqlx::query("SELECT 1").execute(pool.get_ref());
+    report(check("health check", ping).await)
+}
+
+/// Liveness: the process is up and serving requests. Deliberately checks
+/// nothing else, so a slow database gets the pod taken out of rotation by
+/// `readyz` rather than restarted.
+pub async fn livez() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+/// Readiness: the pool can hand out a connection, e.g. not while it's closing
+/// down or the database is unreachable.
+pub async fn readyz(pool: web::Data<PgPool>) -> HttpResponse {
+    report(check("readiness check", pool.acquire()).await)
+}
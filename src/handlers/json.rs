@@ -0,0 +1,279 @@
+use std::{fmt, future::Future, pin::Pin};
+
+use actix_web::{
+    FromRequest, HttpRequest, HttpResponse, ResponseError, dev::Payload, error::JsonPayloadError,
+    http::StatusCode, web,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use validator::{Validate, ValidationErrors};
+
+use crate::handlers::api_error::ApiError;
+
+/// Largest JSON body accepted by default. Product payloads are tiny; this
+/// mostly bounds bulk requests.
+pub const DEFAULT_JSON_LIMIT: usize = 64 * 1024;
+
+/// Caps JSON bodies at `limit` bytes and replaces actix's terse plain-text
+/// body errors with an [`ApiError`] saying what was wrong with the payload.
+pub fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .error_handler(|error, _| {
+            let api_error = match &error {
+                JsonPayloadError::ContentType => ApiError::new(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "request body must be sent as application/json",
+                ),
+                JsonPayloadError::Overflow { limit }
+                | JsonPayloadError::OverflowKnownLength { limit, .. } => ApiError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("request body must be at most {} bytes", limit),
+                ),
+                JsonPayloadError::Deserialize(error) => {
+                    ApiError::bad_request(format!("malformed JSON: {}", error))
+                }
+                _ => ApiError::bad_request("could not read request body"),
+            };
+            actix_web::error::InternalError::from_response(error, api_error.error_response()).into()
+        })
+}
+
+/// JSON body extractor that reports which field failed to deserialize.
+///
+/// Content-type checks, size limits and syntax errors are left to `web::Json`
+/// (and so to the app's `JsonConfig`); only the typed conversion goes through
+/// `serde_path_to_error`, since serde_json alone doesn't say which field was
+/// wrong.
+pub struct Json<T>(pub T);
+impl<T> Json<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+impl<T: DeserializeOwned + 'static> FromRequest for Json<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let value = web::Json::<serde_json::Value>::from_request(req, payload);
+
+        Box::pin(async move {
+            let value = value.await?.into_inner();
+            serde_path_to_error::deserialize(value)
+                .map(Json)
+                .map_err(|error| JsonFieldError::from(error).into())
+        })
+    }
+}
+
+/// [`Json`] that also checks `T`'s `validator` rules, so handlers only see
+/// DTOs that passed them. Failures are reported for every field at once.
+pub struct ValidatedJson<T>(pub T);
+impl<T> ValidatedJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+impl<T: DeserializeOwned + Validate + 'static> FromRequest for ValidatedJson<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let json = Json::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            let value = json.await?.into_inner();
+            value
+                .validate()
+                .map_err(|errors| InvalidFields::from(errors).into())
+                .map(|()| ValidatedJson(value))
+        })
+    }
+}
+
+/// The usual [`ApiError`] body for the first broken rule, plus `fields`
+/// listing every one, ordered by field.
+#[derive(Debug, Serialize)]
+pub struct InvalidFields {
+    error: &'static str,
+    fields: Vec<FieldViolation>,
+}
+#[derive(Debug, Serialize)]
+pub struct FieldViolation {
+    pub field: String,
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+impl InvalidFields {
+    /// The violations, for responses that report them in their own shape.
+    pub fn into_fields(self) -> Vec<FieldViolation> {
+        self.fields
+    }
+}
+impl From<ValidationErrors> for InvalidFields {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields: Vec<_> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldViolation {
+                    field: field.to_string(),
+                    code: error.code.to_string(),
+                    message: error.message.as_ref().map(ToString::to_string),
+                })
+            })
+            .collect();
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+
+        Self {
+            error: "invalid fields",
+            fields,
+        }
+    }
+}
+impl fmt::Display for InvalidFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<_> = self.fields.iter().map(|v| v.field.as_str()).collect();
+        write!(f, "{} at '{}'", self.error, fields.join("', '"))
+    }
+}
+impl ResponseError for InvalidFields {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::UNPROCESSABLE_ENTITY
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        #[derive(Serialize)]
+        struct Body<'a> {
+            #[serde(flatten)]
+            error: ApiError,
+            fields: &'a [FieldViolation],
+        }
+
+        let error = match self.fields.first() {
+            Some(first) => ApiError::invalid_field(
+                &first.field,
+                first
+                    .message
+                    .clone()
+                    .unwrap_or_else(|| format!("{} is invalid", first.field)),
+            ),
+            None => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, self.error),
+        };
+        HttpResponse::UnprocessableEntity().json(Body {
+            error,
+            fields: &self.fields,
+        })
+    }
+}
+
+/// A field of the body that didn't deserialize. Answered as an [`ApiError`]
+/// naming the field, plus a machine-readable `code` and, for type errors, the
+/// JSON type `expected`.
+#[derive(Debug)]
+pub struct JsonFieldError {
+    pub field: String,
+    pub code: &'static str,
+    pub expected: Option<&'static str>,
+}
+impl JsonFieldError {
+    fn message(&self) -> String {
+        let field = &self.field;
+        match (self.code, self.expected) {
+            ("MISSING_FIELD", _) => format!("{} is required", field),
+            ("UNKNOWN_FIELD", _) => format!("unknown field {}", field),
+            (_, Some(expected)) if expected != "unknown" => {
+                let article = match expected {
+                    "integer" | "array" | "object" => "an",
+                    _ => "a",
+                };
+                format!("{} must be {} {}", field, article, expected)
+            }
+            ("INVALID_TYPE", _) => format!("{} has the wrong type", field),
+            _ => format!("{} is invalid", field),
+        }
+    }
+}
+impl From<serde_path_to_error::Error<serde_json::Error>> for JsonFieldError {
+    fn from(error: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let path = error.path().to_string();
+        let message = error.inner().to_string();
+
+        // serde reports missing keys against the parent object, naming the key
+        // only in the message; unknown keys already have their own path.
+        let named_field = message.split('`').nth(1).map(|name| match path.as_str() {
+            "." => name.to_owned(),
+            parent => format!("{}.{}", parent, name),
+        });
+
+        let expected = || {
+            message
+                .rsplit_once(", expected ")
+                .map(|(_, expected)| json_type_name(expected))
+        };
+
+        let (code, field, expected) = if message.starts_with("missing field") {
+            ("MISSING_FIELD", named_field.unwrap_or(path), None)
+        } else if message.starts_with("unknown field") {
+            ("UNKNOWN_FIELD", path, None)
+        } else if message.starts_with("invalid type") {
+            ("INVALID_TYPE", path, expected())
+        } else if message.starts_with("invalid value") || message.starts_with("invalid length") {
+            ("INVALID_VALUE", path, expected())
+        } else {
+            ("INVALID_FIELD", path, None)
+        };
+
+        Self {
+            field,
+            code,
+            expected,
+        }
+    }
+}
+impl fmt::Display for JsonFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at '{}'", self.code, self.field)
+    }
+}
+/// Unknown keys are usually typos in the request's shape rather than bad
+/// values, so they get a 400 instead of a 422.
+impl ResponseError for JsonFieldError {
+    fn status_code(&self) -> StatusCode {
+        match self.code {
+            "UNKNOWN_FIELD" => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        #[derive(Serialize)]
+        struct Body {
+            #[serde(flatten)]
+            error: ApiError,
+            code: &'static str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            expected: Option<&'static str>,
+        }
+
+        HttpResponse::build(self.status_code()).json(Body {
+            error: ApiError::new(self.status_code(), self.message()).with_field(&self.field),
+            code: self.code,
+            expected: self.expected,
+        })
+    }
+}
+
+/// Maps serde's description of the Rust type it wanted onto JSON vocabulary.
+fn json_type_name(expected: &str) -> &'static str {
+    match expected {
+        "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128" => "integer",
+        "f32" | "f64" | "a JSON number" => "number",
+        "a boolean" => "boolean",
+        "a string" | "a borrowed string" | "a character" => "string",
+        "a sequence" => "array",
+        expected if expected.starts_with("struct ") || expected.starts_with("a map") => "object",
+        _ => "unknown",
+    }
+}
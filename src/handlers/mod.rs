@@ -1 +1,8 @@
+pub mod api_error;
+pub mod api_version;
+pub mod category_handlers;
+pub mod health;
+pub mod json;
+pub mod metrics;
 pub mod product_handlers;
+pub mod query;
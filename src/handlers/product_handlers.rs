@@ -1,112 +1,1089 @@
-use actix_web::{HttpResponse, http::header::LOCATION, web};
+use std::{
+    error::Error,
+    mem,
+    pin::pin,
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    FromRequest, HttpMessage, HttpRequest, HttpResponse,
+    http::{
+        StatusCode,
+        header::{
+            ACCEPT, CONTENT_DISPOSITION, ContentType, ETag, EntityTag, Header, IF_MATCH,
+            IfNoneMatch, LOCATION, LastModified, VARY,
+        },
+    },
+    web,
+    web::Bytes,
+};
+use async_stream::try_stream;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::{Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 use crate::{
-    application::product_service::{ProductRepository, ProductService, ProductServiceError},
-    domain::product::Product,
+    application::{
+        fields::{Field, PRODUCT_FIELDS},
+        filtering::ProductFilter,
+        pagination::{Cursor, Page, Paged},
+        product_service::{ProductRepository, ProductService, ProductServiceError},
+        sorting::{InvalidSort, Sort},
+    },
+    domain::{
+        category::CategoryId,
+        price::Price,
+        price_unit::PriceUnit,
+        product::{NewProduct, PriceChange, Product, ProductId, ProductStats},
+        tax_rate::TaxRate,
+    },
+    handlers::{
+        api_error::ApiError,
+        api_version::ApiVersion,
+        json::{InvalidFields, Json, ValidatedJson},
+        query::{BoundedQuery, QueryLimits},
+    },
+    middleware::{auth::AdminUser, request_id::RequestId},
 };
 
 #[derive(Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub window_headers: bool,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// A `next_cursor` from an earlier page; see [`Cursor`].
+    pub after: Option<String>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub min_price: Option<i64>,
+    pub max_price: Option<i64>,
+    pub q: Option<String>,
+    pub category_id: Option<CategoryId>,
+    pub tag: Option<String>,
+    /// Lists soft-deleted products too; admins only.
+    #[serde(default)]
+    pub include_deleted: bool,
+    /// Sends every match as a bare JSON array, streamed as it's read, in
+    /// place of a page.
+    #[serde(default)]
+    pub stream: bool,
+}
+#[derive(Deserialize)]
+pub struct RecentQuery {
+    pub within: String,
+}
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub a: ProductId,
+    pub b: ProductId,
+}
+#[derive(Deserialize)]
+pub struct FindQuery {
+    pub tax_rate: Option<String>,
+    /// Finds a soft-deleted product too; admins only.
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// Rejects the whole import if any row is invalid.
+    #[serde(default)]
+    pub strict: bool,
+}
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
 pub struct CreateProductDTO {
+    #[validate(length(min = 1, max = 255, message = "name must be 1 to 255 characters"))]
     pub name: String,
     pub description: String,
-    pub price: u32,
+    #[validate(custom(function = "non_negative"))]
+    pub price: serde_json::Number,
+    pub price_unit: Option<String>,
+    /// Trimmed and de-duplicated on write.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A `PUT` may set it on a product that has none, or repeat the one it
+    /// has, but not change it.
+    pub sku: Option<String>,
+}
+impl CreateProductDTO {
+    fn price_in_cents(&self, default_unit: PriceUnit) -> Result<Price, ApiError> {
+        price_in_cents(&self.price, self.price_unit.as_deref(), default_unit)
+    }
+}
+/// A partial update; omitted fields keep their current values.
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateProductDTO {
+    #[validate(length(min = 1, max = 255, message = "name must be 1 to 255 characters"))]
+    pub name: Option<String>,
+    pub description: Option<String>,
+    #[validate(custom(function = "non_negative"))]
+    pub price: Option<serde_json::Number>,
+    pub price_unit: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+impl UpdateProductDTO {
+    fn price_in_cents(&self, default_unit: PriceUnit) -> Result<Option<Price>, ApiError> {
+        self.price
+            .as_ref()
+            .map(|price| price_in_cents(price, self.price_unit.as_deref(), default_unit))
+            .transpose()
+    }
+}
+/// The upper bound depends on `price_unit`, so it's left to [`price_in_cents`].
+/// serde_json keeps the number as written (`arbitrary_precision`), so this
+/// looks at the sign rather than going through `f64`.
+fn non_negative(price: &serde_json::Number) -> Result<(), ValidationError> {
+    if price.to_string().starts_with('-') {
+        return Err(ValidationError::new("range")
+            .with_message("price must be a non-negative decimal number".into()));
+    }
+    Ok(())
+}
+/// Normalizes `price` to cents, reading it in `price_unit` or, if the client
+/// didn't say, in the server's default unit.
+fn price_in_cents(
+    price: &serde_json::Number,
+    price_unit: Option<&str>,
+    default_unit: PriceUnit,
+) -> Result<Price, ApiError> {
+    let unit = match price_unit.map(str::parse::<PriceUnit>) {
+        None => default_unit,
+        Some(Ok(unit)) => unit,
+        Some(Err(error)) => return Err(ApiError::invalid_field("price_unit", error)),
+    };
+    unit.to_cents(&price.to_string())
+        .map_err(|error| ApiError::invalid_field("price", error))
+}
+/// Names the product's version, for clients to send back in `If-Match`, and
+/// which representation of it the body is: `"<version>"` for the plain v1
+/// body, with `-v2` and `-tax<basis points>` appended for the others, so a
+/// strong tag never stands for two different bodies.
+fn version_etag(product: &Product, version: ApiVersion, tax_rate: Option<TaxRate>) -> ETag {
+    let mut tag = product.version.to_string();
+    if version == ApiVersion::V2 {
+        tag.push_str("-v2");
+    }
+    if let Some(rate) = tax_rate {
+        tag.push_str(&format!("-tax{}", rate.basis_points()));
+    }
+    ETag(EntityTag::new_strong(tag))
+}
+/// Whether `If-None-Match` already names `etag`, so the client's copy is
+/// current. Uses weak comparison, as GETs may.
+fn client_has(req: &HttpRequest, etag: &EntityTag) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        Err(_) => false,
+    }
+}
+/// The version a conditional write requires, from `If-Match: "<version>"`,
+/// whichever representation's tag (see [`version_etag`]) the client holds.
+/// No header or `*` accepts any version; anything other than a single strong
+/// tag we could have issued can never match.
+fn expected_version(req: &HttpRequest) -> Result<Option<i32>, ApiError> {
+    let Some(value) = req.headers().get(IF_MATCH) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| ApiError::bad_request("invalid If-Match header"))?
+        .trim();
+    if value == "*" {
+        return Ok(None);
+    }
+
+    value
+        .parse::<EntityTag>()
+        .ok()
+        .filter(|tag| !tag.weak)
+        .and_then(|tag| tag.tag().split('-').next()?.parse().ok())
+        .map(Some)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::PRECONDITION_FAILED,
+                "If-Match doesn't name a product version",
+            )
+        })
+}
+/// `null` takes the product out of its category.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssignCategoryDTO {
+    pub category_id: Option<CategoryId>,
+}
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct StockDTO {
+    /// The column is an `INT`.
+    #[validate(range(max = 2147483647, message = "stock must be at most 2147483647"))]
+    pub stock: u32,
+}
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct ReserveDTO {
+    #[validate(range(min = 1, message = "quantity must be at least 1"))]
+    pub quantity: u32,
+}
+#[derive(Deserialize)]
+pub struct BulkDeleteDTO {
+    pub ids: Vec<ProductId>,
+}
+#[derive(Serialize)]
+pub struct BulkDeleteResultDTO {
+    deleted: u64,
+}
+/// One CSV row to import. `tags` and `sku` are optional columns, with tags
+/// separated by `;`. Other columns, such as the `id` and timestamps of an
+/// export, are ignored.
+#[derive(Deserialize)]
+struct CsvProductRow {
+    name: String,
+    description: String,
+    price: String,
+    #[serde(default)]
+    tags: Option<String>,
+    #[serde(default)]
+    sku: Option<String>,
+}
+#[derive(Serialize)]
+pub struct ImportErrorDTO {
+    line: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    message: String,
+}
+#[derive(Serialize)]
+pub struct ImportResultDTO {
+    created: usize,
+    errors: Vec<ImportErrorDTO>,
 }
 #[derive(Serialize)]
 pub struct OutputProductDTO {
-    id: Uuid,
+    id: ProductId,
     name: String,
+    slug: String,
+    sku: Option<String>,
     description: String,
-    price: u32,
+    price: Price,
+    category_id: Option<CategoryId>,
+    tags: Vec<String>,
+    stock: u32,
+    version: i32,
+    /// Only present for soft-deleted products.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Utc>>,
 }
 impl From<Product> for OutputProductDTO {
     fn from(value: Product) -> Self {
         Self {
             id: value.id,
             name: value.name,
+            slug: value.slug,
+            sku: value.sku,
+            description: value.description,
+            price: value.price,
+            category_id: value.category_id,
+            tags: value.tags,
+            stock: value.stock,
+            version: value.version,
+            deleted_at: value.deleted_at,
+        }
+    }
+}
+#[derive(Serialize)]
+pub struct OutputProductV2DTO {
+    id: ProductId,
+    name: String,
+    slug: String,
+    sku: Option<String>,
+    description: String,
+    price: Price,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    category_id: Option<CategoryId>,
+    tags: Vec<String>,
+    stock: u32,
+    version: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Utc>>,
+}
+impl From<Product> for OutputProductV2DTO {
+    fn from(value: Product) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            slug: value.slug,
+            sku: value.sku,
             description: value.description,
             price: value.price,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            category_id: value.category_id,
+            tags: value.tags,
+            stock: value.stock,
+            version: value.version,
+            deleted_at: value.deleted_at,
+        }
+    }
+}
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum VersionedProductDTO {
+    V1(OutputProductDTO),
+    V2(OutputProductV2DTO),
+}
+impl VersionedProductDTO {
+    pub fn new(version: ApiVersion, product: Product) -> Self {
+        match version {
+            ApiVersion::V1 => Self::V1(product.into()),
+            ApiVersion::V2 => Self::V2(product.into()),
+        }
+    }
+}
+#[derive(Serialize)]
+pub struct TaxedProductDTO {
+    #[serde(flatten)]
+    product: VersionedProductDTO,
+    gross_price: u32,
+}
+/// `limit` and `offset` are the values actually used, which may differ from
+/// the requested ones after clamping.
+#[derive(Serialize)]
+pub struct PagedResponse<T> {
+    items: Vec<T>,
+    total: u64,
+    limit: u32,
+    offset: u32,
+    /// Pass as `after` for the next page. Only given for full pages in the
+    /// default order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+#[derive(Serialize)]
+pub struct FieldCapabilityDTO {
+    name: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    sortable: bool,
+    filterable: bool,
+}
+impl From<&Field> for FieldCapabilityDTO {
+    fn from(value: &Field) -> Self {
+        Self {
+            name: value.name,
+            kind: value.kind.as_str(),
+            sortable: value.sortable,
+            filterable: value.filterable,
+        }
+    }
+}
+#[derive(Serialize)]
+pub struct CapabilitiesDTO {
+    default_page_size: u32,
+    max_page_size: u32,
+    max_result_rows: u32,
+    max_json_body_bytes: usize,
+    max_query_params: usize,
+    max_query_length: usize,
+    default_price_unit: &'static str,
+    price_units: Vec<&'static str>,
+    fields: Vec<FieldCapabilityDTO>,
+}
+#[derive(Serialize)]
+pub struct OutputPriceChangeDTO {
+    old_price: Price,
+    new_price: Price,
+    changed_at: DateTime<Utc>,
+}
+impl From<PriceChange> for OutputPriceChangeDTO {
+    fn from(value: PriceChange) -> Self {
+        Self {
+            old_price: value.old_price,
+            new_price: value.new_price,
+            changed_at: value.changed_at,
         }
     }
 }
+/// Prices are in cents, like everywhere else, and `null` when there are no
+/// products.
+#[derive(Serialize)]
+pub struct OutputStatsDTO {
+    count: u64,
+    min_price: Option<Price>,
+    max_price: Option<Price>,
+    average_price: Option<Price>,
+}
+impl From<ProductStats> for OutputStatsDTO {
+    fn from(value: ProductStats) -> Self {
+        Self {
+            count: value.count,
+            min_price: value.min_price,
+            max_price: value.max_price,
+            average_price: value.average_price,
+        }
+    }
+}
+
+pub const OLDEST_CREATED_HEADER: &str = "X-Oldest-Created";
+pub const NEWEST_UPDATED_HEADER: &str = "X-Newest-Updated";
 
-pub async fn list_products<R: ProductRepository>(
+pub async fn list_products<R: ProductRepository + 'static>(
+    req: HttpRequest,
     service: web::Data<ProductService<R>>,
-) -> HttpResponse {
-    match service.list().await {
-        Ok(products) => HttpResponse::Ok().json(
-            products
-                .into_iter()
-                .map(|product| OutputProductDTO::from(product))
-                .collect::<Vec<_>>(),
-        ),
-        Err(error) => {
-            log::error!("error while listing products: {}", error);
-            HttpResponse::InternalServerError().finish()
+    query: BoundedQuery<ListQuery>,
+    version: ApiVersion,
+) -> actix_web::Result<HttpResponse> {
+    if query.include_deleted {
+        AdminUser::extract(&req).await?;
+    }
+    let sort = Sort::parse(query.sort.as_deref(), query.order.as_deref()).map_err(|error| {
+        let field = match error {
+            InvalidSort::Field(_) => "sort",
+            InvalidSort::Order(_) => "order",
+        };
+        ApiError::bad_request(error).with_field(field)
+    })?;
+    let filter = ProductFilter::default()
+        .with_search(query.q.clone())
+        .with_category(query.category_id)
+        .with_tag(query.tag.clone())
+        .with_range("price", query.min_price, query.max_price)
+        .map_err(ApiError::bad_request)?;
+
+    if query.stream {
+        if query.limit.is_some() || query.offset.is_some() || query.after.is_some() {
+            return Err(ApiError::bad_request(
+                "stream returns every match, so it takes no limit, offset or after",
+            )
+            .into());
         }
+        let chunks = json_array_chunks(service, filter, sort, query.include_deleted, version);
+        return Ok(HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .streaming(log_stream_errors(chunks, "product stream")));
     }
+
+    let after = query
+        .after
+        .as_deref()
+        .map(Cursor::decode)
+        .transpose()
+        .map_err(|error| ApiError::bad_request(error).with_field("after"))?;
+    if after.is_some() && query.offset.is_some() {
+        return Err(ApiError::bad_request("after and offset can't be combined")
+            .with_field("after")
+            .into());
+    }
+    // The cursor is a position in the default order only.
+    let keyset = sort == Sort::default();
+    if after.is_some() && !keyset {
+        return Err(ApiError::bad_request(
+            "after only works in the default order, by updated_at desc",
+        )
+        .with_field("after")
+        .into());
+    }
+    let page = Page::new(query.limit, query.offset).with_after(after);
+
+    let Paged {
+        items: products,
+        total,
+        page,
+    } = service
+        .list(&filter, sort, page, query.include_deleted)
+        .await
+        .map_err(ProductServiceError::Repository)?;
+
+    let mut response = HttpResponse::Ok();
+    if query.window_headers {
+        // Computed from the page itself so incremental-sync clients can
+        // see the time window they fetched without scanning the body.
+        if let Some(oldest) = products.iter().map(|p| p.created_at).min() {
+            response.insert_header((
+                OLDEST_CREATED_HEADER,
+                oldest.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            ));
+        }
+        if let Some(newest) = products.iter().map(|p| p.updated_at).max() {
+            response.insert_header((
+                NEWEST_UPDATED_HEADER,
+                newest.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            ));
+        }
+    }
+
+    let next_cursor = products
+        .last()
+        .filter(|_| keyset && products.len() == page.limit as usize)
+        .map(|last| Cursor::from(last).encode());
+    Ok(response.json(PagedResponse {
+        items: products
+            .into_iter()
+            .map(|product| VersionedProductDTO::new(version, product))
+            .collect(),
+        total,
+        limit: page.limit,
+        offset: page.offset,
+        next_cursor,
+    }))
 }
 
-pub async fn add_product<R: ProductRepository>(
+pub async fn product_stats<R: ProductRepository>(
     service: web::Data<ProductService<R>>,
-    payload: web::Json<CreateProductDTO>,
-) -> HttpResponse {
-    let dto = payload.into_inner();
-    match service.add(dto.name, dto.description, dto.price).await {
-        Ok(product) => HttpResponse::Created()
-            .insert_header((LOCATION, format!("/api/products/{}", product.id)))
-            .json(OutputProductDTO::from(product)),
-        Err(error) => {
-            log::error!("error while creating product: {}", error);
-            HttpResponse::InternalServerError().finish()
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let stats = service
+        .stats()
+        .await
+        .map_err(ProductServiceError::Repository)?;
+    Ok(HttpResponse::Ok().json(OutputStatsDTO::from(stats)))
+}
+
+/// The configured limits `capabilities` reports; see [`Config::limits`].
+///
+/// [`Config::limits`]: crate::config::Config::limits
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub max_result_rows: u32,
+    pub max_json_body_bytes: usize,
+    pub query: QueryLimits,
+    pub default_price_unit: PriceUnit,
+}
+
+/// Advertises the effective limits and list query options. Fields come
+/// straight from the field registry so they can't drift from what
+/// `list_products` accepts.
+pub async fn capabilities(limits: web::Data<Limits>) -> HttpResponse {
+    HttpResponse::Ok().json(CapabilitiesDTO {
+        default_page_size: Page::DEFAULT_LIMIT,
+        max_page_size: Page::MAX_LIMIT,
+        max_result_rows: limits.max_result_rows,
+        max_json_body_bytes: limits.max_json_body_bytes,
+        max_query_params: limits.query.max_params,
+        max_query_length: limits.query.max_length,
+        default_price_unit: limits.default_price_unit.as_str(),
+        price_units: PriceUnit::ALL.iter().map(PriceUnit::as_str).collect(),
+        fields: PRODUCT_FIELDS.iter().map(Into::into).collect(),
+    })
+}
+
+pub const CSV_COLUMNS: [&str; 6] = [
+    "id",
+    "name",
+    "description",
+    "price",
+    "created_at",
+    "updated_at",
+];
+/// Streamed responses send what they've encoded once it adds up to this much.
+const STREAM_CHUNK_BYTES: usize = 8 * 1024;
+
+/// Encodes every product as CSV, a chunk at a time. The header row is always
+/// there, even for an empty catalog.
+fn csv_chunks<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+) -> impl Stream<Item = Result<Bytes, Box<dyn Error>>> {
+    let new_writer = || csv::Writer::from_writer(Vec::with_capacity(STREAM_CHUNK_BYTES));
+    try_stream! {
+        let mut writer = new_writer();
+        writer.write_record(CSV_COLUMNS)?;
+
+        let mut products = pin!(service.export());
+        while let Some(product) = products.try_next().await? {
+            writer.write_record([
+                product.id.to_string(),
+                product.name,
+                product.description,
+                product.price.cents().to_string(),
+                product.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                product.updated_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            ])?;
+            // The writer buffers internally; flushing moves the row into the
+            // `Vec` so its length is accurate.
+            writer.flush()?;
+            if writer.get_ref().len() >= STREAM_CHUNK_BYTES {
+                let chunk = mem::replace(&mut writer, new_writer()).into_inner()?;
+                yield Bytes::from(chunk);
+            }
         }
+
+        yield Bytes::from(writer.into_inner()?);
     }
 }
 
-pub async fn find_product<R: ProductRepository>(
+const CSV_IMPORT_COLUMNS: [&str; 3] = ["name", "description", "price"];
+
+/// Checks a CSV row against the rules a JSON create goes through. Prices are
+/// in cents, as the export writes them.
+fn import_row(
+    record: &csv::StringRecord,
+    headers: &csv::StringRecord,
+) -> Result<NewProduct, Vec<ImportErrorDTO>> {
+    let line = record.position().map_or(0, |position| position.line());
+    let error = |field: Option<&str>, message: String| ImportErrorDTO {
+        line,
+        field: field.map(ToOwned::to_owned),
+        message,
+    };
+
+    let row: CsvProductRow = record
+        .deserialize(Some(headers))
+        .map_err(|e| vec![error(None, e.to_string())])?;
+    let price = row.price.trim().parse().map_err(|_| {
+        vec![error(
+            Some("price"),
+            "price must be a non-negative decimal number".into(),
+        )]
+    })?;
+    let dto = CreateProductDTO {
+        name: row.name.trim().to_owned(),
+        description: row.description,
+        price,
+        price_unit: None,
+        tags: row
+            .tags
+            .map(|tags| tags.split(';').map(ToOwned::to_owned).collect())
+            .unwrap_or_default(),
+        sku: row.sku,
+    };
+    dto.validate().map_err(|errors| {
+        InvalidFields::from(errors)
+            .into_fields()
+            .into_iter()
+            .map(|v| error(Some(&v.field), v.message.unwrap_or(v.code)))
+            .collect::<Vec<_>>()
+    })?;
+    let price = PriceUnit::Cents
+        .to_cents(&dto.price.to_string())
+        .map_err(|e| vec![error(Some("price"), e.to_string())])?;
+
+    Ok(NewProduct {
+        name: dto.name,
+        description: dto.description,
+        price,
+        tags: dto.tags,
+        sku: dto.sku,
+    })
+}
+
+/// Creates a product per CSV row, all in one transaction. Invalid rows are
+/// reported by line and skipped, or with `strict=true` fail the import with
+/// nothing created.
+pub async fn import_products<R: ProductRepository>(
+    req: HttpRequest,
     service: web::Data<ProductService<R>>,
-    id: web::Path<Uuid>,
-) -> HttpResponse {
-    match service.find(id.into_inner()).await {
-        Ok(product) => HttpResponse::Ok().json(OutputProductDTO::from(product)),
-        Err(ProductServiceError::NotFound) => HttpResponse::NotFound().finish(),
-        Err(ProductServiceError::Repository(error)) => {
-            log::error!("error while getting product: {}", error);
-            HttpResponse::InternalServerError().finish()
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> actix_web::Result<HttpResponse> {
+    let is_csv = req
+        .mime_type()
+        .ok()
+        .flatten()
+        .is_some_and(|mime| mime.essence_str() == "text/csv");
+    if !is_csv {
+        return Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "request body must be sent as text/csv",
+        )
+        .into());
+    }
+
+    let mut reader = csv::Reader::from_reader(body.as_ref());
+    let headers = reader
+        .headers()
+        .map_err(|e| ApiError::bad_request(format!("malformed CSV: {}", e)))?
+        .clone();
+    if let Some(missing) = CSV_IMPORT_COLUMNS
+        .into_iter()
+        .find(|column| !headers.iter().any(|header| header == *column))
+    {
+        return Err(
+            ApiError::bad_request(format!("CSV header has no '{}' column", missing)).into(),
+        );
+    }
+
+    let mut products = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let row = record
+            .map_err(|e| {
+                vec![ImportErrorDTO {
+                    line: e.position().map_or(0, |position| position.line()),
+                    field: None,
+                    message: e.to_string(),
+                }]
+            })
+            .and_then(|record| import_row(&record, &headers));
+        match row {
+            Ok(product) => products.push(product),
+            Err(row_errors) => errors.extend(row_errors),
         }
     }
+
+    if query.strict && !errors.is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(ImportResultDTO { created: 0, errors }));
+    }
+    let created = service.import(products).await?.len();
+    Ok(HttpResponse::Ok().json(ImportResultDTO { created, errors }))
 }
 
-pub async fn put_product<R: ProductRepository>(
+/// Encodes the matching products as a JSON array, a chunk at a time.
+fn json_array_chunks<R: ProductRepository>(
     service: web::Data<ProductService<R>>,
-    id: web::Path<Uuid>,
-    payload: web::Json<CreateProductDTO>,
-) -> HttpResponse {
-    let dto = payload.into_inner();
-    match service
-        .modify(id.into_inner(), dto.name, dto.description, dto.price)
-        .await
-    {
-        Ok(product) => HttpResponse::Ok().json(OutputProductDTO::from(product)),
-        Err(ProductServiceError::NotFound) => HttpResponse::NotFound().finish(),
-        Err(ProductServiceError::Repository(error)) => {
-            log::error!("error while modifying product: {}", error);
-            HttpResponse::InternalServerError().finish()
+    filter: ProductFilter,
+    sort: Sort,
+    include_deleted: bool,
+    version: ApiVersion,
+) -> impl Stream<Item = Result<Bytes, Box<dyn Error>>> {
+    try_stream! {
+        let mut chunk = Vec::with_capacity(STREAM_CHUNK_BYTES);
+        chunk.push(b'[');
+
+        let mut products = pin!(service.stream(&filter, sort, include_deleted));
+        let mut first = true;
+        while let Some(product) = products.try_next().await? {
+            if !mem::take(&mut first) {
+                chunk.push(b',');
+            }
+            serde_json::to_writer(&mut chunk, &VersionedProductDTO::new(version, product))?;
+            if chunk.len() >= STREAM_CHUNK_BYTES {
+                yield Bytes::from(mem::take(&mut chunk));
+            }
         }
+
+        chunk.push(b']');
+        yield Bytes::from(chunk);
     }
 }
 
-pub async fn remove_product<R: ProductRepository>(
+/// The status and headers of a streamed response go out before the body is
+/// done, so a failure partway can only cut it short. It's logged instead.
+fn log_stream_errors<T>(
+    chunks: impl Stream<Item = Result<T, Box<dyn Error>>>,
+    what: &'static str,
+) -> impl Stream<Item = Result<T, Box<dyn Error>>> {
+    let request_id = RequestId::current();
+    chunks
+        .inspect_err(move |error| log::error!("request {}: {} failed: {}", request_id, what, error))
+}
+
+/// Streams the whole catalog as CSV, so exporting a large table doesn't load
+/// it into memory.
+pub async fn export_products<R: ProductRepository + 'static>(
     service: web::Data<ProductService<R>>,
-    id: web::Path<Uuid>,
 ) -> HttpResponse {
-    match service.remove(id.into_inner()).await {
-        Ok(()) => HttpResponse::NoContent().finish(),
-        Err(ProductServiceError::NotFound) => HttpResponse::NotFound().finish(),
-        Err(ProductServiceError::Repository(error)) => {
-            log::error!("error while deleting product: {}", error);
-            HttpResponse::InternalServerError().finish()
-        }
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((CONTENT_DISPOSITION, "attachment; filename=\"products.csv\""))
+        .streaming(log_stream_errors(csv_chunks(service), "CSV export"))
+}
+
+/// Parses windows such as `90s`, `15m`, `1h` or `7d`.
+fn parse_window(value: &str) -> Option<Duration> {
+    let unit_index = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(unit_index);
+    let amount: u64 = amount.parse().ok().filter(|amount| *amount > 0)?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+
+    amount.checked_mul(unit_secs).map(Duration::from_secs)
+}
+
+pub async fn list_recent_products<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    query: web::Query<RecentQuery>,
+    version: ApiVersion,
+) -> actix_web::Result<HttpResponse> {
+    let within = parse_window(&query.within).ok_or_else(|| {
+        ApiError::bad_request("within must look like 90s, 15m, 1h or 7d").with_field("within")
+    })?;
+
+    let products = service
+        .list_recent(within)
+        .await
+        .map_err(ProductServiceError::Repository)?;
+    Ok(HttpResponse::Ok().json(
+        products
+            .into_iter()
+            .map(|product| VersionedProductDTO::new(version, product))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+pub async fn add_product<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    default_unit: web::Data<PriceUnit>,
+    payload: ValidatedJson<CreateProductDTO>,
+    version: ApiVersion,
+) -> actix_web::Result<HttpResponse> {
+    let dto = payload.into_inner();
+    let price = dto.price_in_cents(**default_unit)?;
+
+    let product = service
+        .add(dto.name, dto.description, price, dto.tags, dto.sku)
+        .await?;
+    Ok(HttpResponse::Created()
+        .insert_header((LOCATION, format!("/api/products/{}", product.id)))
+        .insert_header(version_etag(&product, version, None))
+        .json(VersionedProductDTO::new(version, product)))
+}
+
+/// Answers `If-None-Match` with a bodiless 304 when the product hasn't
+/// changed since the client fetched it. `include_deleted` is ignored for
+/// anyone but admins, so a deleted product stays a 404 to everyone else.
+pub async fn find_product<R: ProductRepository>(
+    req: HttpRequest,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    query: web::Query<FindQuery>,
+    version: ApiVersion,
+) -> actix_web::Result<HttpResponse> {
+    let query = query.into_inner();
+    let include_deleted = query.include_deleted && AdminUser::extract(&req).await.is_ok();
+    let tax_rate = query
+        .tax_rate
+        .as_deref()
+        .map(str::parse::<TaxRate>)
+        .transpose()
+        .map_err(|error| ApiError::bad_request(error).with_field("tax_rate"))?;
+
+    let product = service.find(id.into_inner(), include_deleted).await?;
+    let etag = version_etag(&product, version, tax_rate);
+    let last_modified = LastModified(SystemTime::from(product.updated_at).into());
+    // The body depends on `Accept` (see `ApiVersion`), so caches must key on
+    // it, for 304s as much as for full responses.
+    if client_has(&req, &etag.0) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(etag)
+            .insert_header(last_modified)
+            .insert_header((VARY, ACCEPT.as_str()))
+            .finish());
     }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header(etag)
+        .insert_header(last_modified)
+        .insert_header((VARY, ACCEPT.as_str()));
+    Ok(match tax_rate {
+        Some(rate) => response.json(TaxedProductDTO {
+            gross_price: product.price_with_tax(rate),
+            product: VersionedProductDTO::new(version, product),
+        }),
+        None => response.json(VersionedProductDTO::new(version, product)),
+    })
+}
+
+pub async fn find_product_by_sku<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    sku: web::Path<String>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let product = service.find_by_sku(&sku).await?;
+    Ok(HttpResponse::Ok().json(VersionedProductDTO::new(version, product)))
+}
+
+pub async fn find_product_by_slug<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    slug: web::Path<String>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let product = service.find_by_slug(&slug).await?;
+    Ok(HttpResponse::Ok().json(VersionedProductDTO::new(version, product)))
+}
+
+/// Compares the serialized shapes field by field, so any field added to the
+/// output DTOs shows up in diffs without touching this code. Identifiers are
+/// unique per product, so they're left out.
+fn diff_fields(
+    a: serde_json::Value,
+    b: serde_json::Value,
+) -> serde_json::Map<String, serde_json::Value> {
+    let (serde_json::Value::Object(a), serde_json::Value::Object(mut b)) = (a, b) else {
+        return serde_json::Map::new();
+    };
+
+    a.into_iter()
+        .filter(|(field, _)| !matches!(field.as_str(), "id" | "slug" | "sku"))
+        .filter_map(|(field, a_value)| {
+            let b_value = b.remove(&field).unwrap_or_default();
+            (a_value != b_value).then(|| (field, serde_json::json!({ "a": a_value, "b": b_value })))
+        })
+        .collect()
+}
+
+pub async fn diff_products<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    query: web::Query<DiffQuery>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let (a, b) = tokio::try_join!(service.find(query.a, false), service.find(query.b, false))?;
+
+    let to_value = |product| {
+        serde_json::to_value(VersionedProductDTO::new(version, product)).unwrap_or_default()
+    };
+    Ok(HttpResponse::Ok().json(diff_fields(to_value(a), to_value(b))))
+}
+
+pub async fn put_product<R: ProductRepository>(
+    _admin: AdminUser,
+    req: HttpRequest,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    default_unit: web::Data<PriceUnit>,
+    payload: ValidatedJson<CreateProductDTO>,
+    version: ApiVersion,
+) -> actix_web::Result<HttpResponse> {
+    let expected_version = expected_version(&req)?;
+    let dto = payload.into_inner();
+    let price = dto.price_in_cents(**default_unit)?;
+
+    let product = service
+        .modify(
+            id.into_inner(),
+            NewProduct {
+                name: dto.name,
+                description: dto.description,
+                price,
+                tags: dto.tags,
+                sku: dto.sku,
+            },
+            expected_version,
+        )
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(&product, version, None))
+        .json(VersionedProductDTO::new(version, product)))
+}
+
+pub async fn patch_product<R: ProductRepository>(
+    _admin: AdminUser,
+    req: HttpRequest,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    default_unit: web::Data<PriceUnit>,
+    payload: ValidatedJson<UpdateProductDTO>,
+    version: ApiVersion,
+) -> actix_web::Result<HttpResponse> {
+    let expected_version = expected_version(&req)?;
+    let dto = payload.into_inner();
+    let price = dto.price_in_cents(**default_unit)?;
+
+    let product = service
+        .modify_partial(
+            id.into_inner(),
+            dto.name,
+            dto.description,
+            price,
+            dto.tags,
+            expected_version,
+        )
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(&product, version, None))
+        .json(VersionedProductDTO::new(version, product)))
+}
+
+pub async fn remove_product<R: ProductRepository>(
+    _admin: AdminUser,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    service.remove(id.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Ids that don't exist or are already deleted are skipped, not errors.
+pub async fn bulk_delete_products<R: ProductRepository>(
+    _admin: AdminUser,
+    service: web::Data<ProductService<R>>,
+    payload: Json<BulkDeleteDTO>,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let ids = payload.into_inner().ids;
+    let deleted = service
+        .remove_many(&ids)
+        .await
+        .map_err(ProductServiceError::Repository)?;
+    Ok(HttpResponse::Ok().json(BulkDeleteResultDTO { deleted }))
+}
+
+/// Moves the product into a category, or out of any with `null`.
+pub async fn assign_category<R: ProductRepository>(
+    _admin: AdminUser,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    payload: Json<AssignCategoryDTO>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let product = service
+        .assign_category(id.into_inner(), payload.into_inner().category_id)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(&product, version, None))
+        .json(VersionedProductDTO::new(version, product)))
+}
+
+pub async fn set_stock<R: ProductRepository>(
+    _admin: AdminUser,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    payload: ValidatedJson<StockDTO>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let product = service
+        .set_stock(id.into_inner(), payload.into_inner().stock)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(&product, version, None))
+        .json(VersionedProductDTO::new(version, product)))
+}
+
+/// Takes units out of stock, or answers 409 with nothing taken if there
+/// aren't enough.
+pub async fn reserve_stock<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    payload: ValidatedJson<ReserveDTO>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let product = service
+        .reserve(id.into_inner(), payload.into_inner().quantity)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(version_etag(&product, version, None))
+        .json(VersionedProductDTO::new(version, product)))
+}
+
+/// The product comes back with `category_id: null` if its category was
+/// deleted while it was, and answers 409 if another product took its SKU.
+pub async fn restore_product<R: ProductRepository>(
+    _admin: AdminUser,
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+    version: ApiVersion,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let product = service.restore(id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(VersionedProductDTO::new(version, product)))
+}
+
+pub async fn price_history<R: ProductRepository>(
+    service: web::Data<ProductService<R>>,
+    id: web::Path<ProductId>,
+) -> Result<HttpResponse, ProductServiceError<R::Error>> {
+    let history = service.price_history(id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(
+        history
+            .into_iter()
+            .map(OutputPriceChangeDTO::from)
+            .collect::<Vec<_>>(),
+    ))
 }
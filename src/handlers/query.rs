@@ -0,0 +1,76 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use serde::de::DeserializeOwned;
+
+use crate::handlers::api_error::ApiError;
+
+/// Caps on a query string, checked before it's parsed. The defaults leave
+/// plenty of room for every list filter at once.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryLimits {
+    pub max_params: usize,
+    /// In bytes, still percent-encoded.
+    pub max_length: usize,
+}
+impl QueryLimits {
+    pub const DEFAULT_MAX_PARAMS: usize = 32;
+    pub const DEFAULT_MAX_LENGTH: usize = 2048;
+
+    fn check(&self, query: &str) -> Result<(), ApiError> {
+        if query.len() > self.max_length {
+            return Err(ApiError::bad_request(format!(
+                "query string must be at most {} bytes",
+                self.max_length
+            )));
+        }
+        let params = query.split('&').filter(|param| !param.is_empty()).count();
+        if params > self.max_params {
+            return Err(ApiError::bad_request(format!(
+                "query string must have at most {} parameters",
+                self.max_params
+            )));
+        }
+        Ok(())
+    }
+}
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_params: Self::DEFAULT_MAX_PARAMS,
+            max_length: Self::DEFAULT_MAX_LENGTH,
+        }
+    }
+}
+
+/// `web::Query` that first rejects query strings over the app's
+/// [`QueryLimits`] (or the defaults, if none are registered) with a 400, so a
+/// flood of parameters is never parsed.
+pub struct BoundedQuery<T>(pub T);
+impl<T> BoundedQuery<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+impl<T> std::ops::Deref for BoundedQuery<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T: DeserializeOwned + 'static> FromRequest for BoundedQuery<T> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let limits = req.app_data::<QueryLimits>().copied().unwrap_or_default();
+        let checked = limits.check(req.query_string());
+        let query = web::Query::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            checked?;
+            Ok(BoundedQuery(query.await?.into_inner()))
+        })
+    }
+}
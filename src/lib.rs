@@ -2,5 +2,8 @@ pub mod domain;
 
 pub mod application;
 
+pub mod config;
+
 pub mod handlers;
+pub mod middleware;
 pub mod repositories;
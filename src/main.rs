@@ -1,57 +1,228 @@
-use std::{
-    env::{self, VarError},
-    error::Error as StdError,
-};
+use std::{error::Error as StdError, time::Duration};
 
-use actix_cors::Cors;
 use actix_web::{
     App, HttpServer,
+    middleware::from_fn,
     web::{self, Data},
 };
-use sqlx::PgPool;
+use sqlx::{
+    PgPool,
+    migrate::{Migrate, MigrateError},
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
 
 use rust_backend::{
-    application::product_service::ProductService,
-    handlers::product_handlers::{
-        add_product, find_product, list_products, put_product, remove_product,
+    application::{category_service::CategoryService, product_service::ProductService},
+    config::Config,
+    handlers::{
+        category_handlers::{
+            add_category, find_category, list_categories, put_category, remove_category,
+        },
+        health::{health, livez, readyz},
+        json::json_config,
+        metrics::metrics,
+        product_handlers::{
+            add_product, assign_category, bulk_delete_products, capabilities, diff_products,
+            export_products, find_product, find_product_by_sku, find_product_by_slug,
+            import_products, list_products, list_recent_products, patch_product, price_history,
+            product_stats, put_product, remove_product, reserve_stock, restore_product, set_stock,
+        },
+    },
+    middleware::{
+        auth::{Authenticator, authenticate},
+        cors::cors,
+        https::enforce_https,
+        metrics::{Metrics, track_metrics},
+        request_id::request_id,
+    },
+    repositories::{
+        category_repository::PgCategoryRepository,
+        product_repository::{PgProductRepository, with_statement_timeout},
     },
-    repositories::product_repository::PgProductRepository,
 };
 
+/// Backoff between startup connection attempts never grows past this, so a
+/// long retry budget doesn't leave the service idle for minutes at a time.
+const MAX_STARTUP_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Gives the database a chance to come up when both are started together
+/// (e.g. by docker-compose), doubling the delay after each failed attempt up
+/// to `MAX_STARTUP_RETRY_DELAY`.
+async fn connect_with_retry(
+    pool_options: &PgPoolOptions,
+    options: &PgConnectOptions,
+    retries: u32,
+    mut delay: Duration,
+) -> Result<PgPool, sqlx::Error> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match pool_options.clone().connect_with(options.clone()).await {
+            Ok(pool) => return Ok(pool),
+            Err(error) if attempt <= retries => {
+                log::warn!(
+                    "database connection attempt {}/{} failed: {}; retrying in {:?}",
+                    attempt,
+                    retries + 1,
+                    error,
+                    delay
+                );
+                actix_web::rt::time::sleep(delay).await;
+                // A configured delay already above the cap is kept as is.
+                delay = delay
+                    .saturating_mul(2)
+                    .min(MAX_STARTUP_RETRY_DELAY)
+                    .max(delay);
+            }
+            Err(error) => {
+                log::error!(
+                    "giving up on the database after {} attempts: {}",
+                    attempt,
+                    error
+                );
+                return Err(error);
+            }
+        }
+    }
+}
+
+async fn applied_migrations(pool: &PgPool) -> Result<usize, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    Ok(conn.list_applied_migrations().await?.len())
+}
+
+/// Brings the schema up to date, returning how many migrations were applied.
+async fn run_migrations(pool: &PgPool) -> Result<usize, MigrateError> {
+    let before = applied_migrations(pool).await?;
+    sqlx::migrate!("./migrations").run(pool).await?;
+    Ok(applied_migrations(pool).await? - before)
+}
+
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn StdError>> {
     let _ = dotenvy::dotenv();
 
     env_logger::init();
 
-    let host = "127.0.0.1";
-    let port = match env::var("PORT") {
-        Err(VarError::NotPresent) => 8080u16,
-        result => result?.parse()?,
-    };
+    let config = Config::from_env()?;
+
+    let mut connect_options: PgConnectOptions = config.database_url.parse()?;
+    if let Some(timeout) = config.statement_timeout {
+        connect_options = with_statement_timeout(connect_options, timeout);
+    }
+    let pool = config.pool;
+    log::info!(
+        "database pool: {}-{} connections, acquire timeout {:?}, idle timeout {:?}",
+        pool.min_connections,
+        pool.max_connections,
+        pool.acquire_timeout,
+        pool.idle_timeout
+    );
+    let pg_pool = connect_with_retry(
+        &pool.options(),
+        &connect_options,
+        config.startup_db_retries,
+        config.startup_db_retry_delay,
+    )
+    .await?;
+
+    if config.run_migrations {
+        let applied = run_migrations(&pg_pool).await?;
+        log::info!("applied {} database migration(s)", applied);
+    }
+
+    let limits = Data::new(config.limits());
+    let Config {
+        host,
+        port,
+        max_result_rows,
+        max_json_body_bytes,
+        query_limits,
+        default_price_unit,
+        regenerate_slugs,
+        touch_on_restore,
+        cors_allowed_headers,
+        https_policy,
+        auth,
+        ..
+    } = config;
 
-    let postgres_url = env::var("DATABASE_URL")?;
-    let pg_pool = PgPool::connect(&postgres_url).await?;
+    // Shared by all workers, so each scrape sees every request.
+    let request_metrics = Data::new(Metrics::default());
+    log::info!("requiring bearer tokens for: {:?}", auth.scope);
+    let authenticator = Data::new(Authenticator::new(&auth));
 
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header()
-            .max_age(3600);
+        let cors = cors(cors_allowed_headers.clone());
 
         type Repo = PgProductRepository;
-        let repo = Repo::new(pg_pool.clone());
+        let repo = Repo::new(pg_pool.clone())
+            .with_max_rows(max_result_rows)
+            .with_slug_regeneration(regenerate_slugs)
+            .with_restore_touch(touch_on_restore);
         let service = ProductService::new(repo);
+        type Categories = PgCategoryRepository;
+        let categories = CategoryService::new(Categories::new(pg_pool.clone()));
 
-        App::new().wrap(cors).app_data(Data::new(service)).service(
-            web::scope("/api/products")
-                .route("", web::get().to(list_products::<Repo>))
-                .route("", web::post().to(add_product::<Repo>))
-                .route("/{id}", web::get().to(find_product::<Repo>))
-                .route("/{id}", web::put().to(put_product::<Repo>))
-                .route("/{id}", web::delete().to(remove_product::<Repo>)),
-        )
+        // Probes and scrapes reach the app over plain HTTP from inside the
+        // network, so only the API scopes enforce HTTPS.
+        App::new()
+            .wrap(cors)
+            .wrap(from_fn(track_metrics))
+            .wrap(from_fn(request_id))
+            .app_data(Data::new(service))
+            .app_data(Data::new(categories))
+            .app_data(Data::new(default_price_unit))
+            .app_data(Data::new(pg_pool.clone()))
+            .app_data(request_metrics.clone())
+            .app_data(authenticator.clone())
+            .app_data(json_config(max_json_body_bytes))
+            .app_data(query_limits)
+            .app_data(limits.clone())
+            .route("/health", web::get().to(health))
+            .route("/livez", web::get().to(livez))
+            .route("/readyz", web::get().to(readyz))
+            .route("/metrics", web::get().to(metrics))
+            .service(
+                web::scope("/api/products")
+                    .wrap(from_fn(authenticate))
+                    .wrap(from_fn(move |req, next| {
+                        enforce_https(https_policy, req, next)
+                    }))
+                    .route("", web::get().to(list_products::<Repo>))
+                    .route("", web::post().to(add_product::<Repo>))
+                    .route("/capabilities", web::get().to(capabilities))
+                    .route("/stats", web::get().to(product_stats::<Repo>))
+                    .route("/recent", web::get().to(list_recent_products::<Repo>))
+                    .route("/diff", web::get().to(diff_products::<Repo>))
+                    .route("/export.csv", web::get().to(export_products::<Repo>))
+                    .route("/import", web::post().to(import_products::<Repo>))
+                    .route("/bulk-delete", web::post().to(bulk_delete_products::<Repo>))
+                    .route("/slug/{slug}", web::get().to(find_product_by_slug::<Repo>))
+                    .route("/by-sku/{sku}", web::get().to(find_product_by_sku::<Repo>))
+                    .route("/{id}", web::get().to(find_product::<Repo>))
+                    .route("/{id}", web::put().to(put_product::<Repo>))
+                    .route("/{id}", web::patch().to(patch_product::<Repo>))
+                    .route("/{id}", web::delete().to(remove_product::<Repo>))
+                    .route("/{id}/restore", web::post().to(restore_product::<Repo>))
+                    .route("/{id}/category", web::put().to(assign_category::<Repo>))
+                    .route("/{id}/stock", web::put().to(set_stock::<Repo>))
+                    .route("/{id}/reserve", web::post().to(reserve_stock::<Repo>))
+                    .route("/{id}/price-history", web::get().to(price_history::<Repo>)),
+            )
+            .service(
+                web::scope("/api/categories")
+                    .wrap(from_fn(authenticate))
+                    .wrap(from_fn(move |req, next| {
+                        enforce_https(https_policy, req, next)
+                    }))
+                    .route("", web::get().to(list_categories::<Categories>))
+                    .route("", web::post().to(add_category::<Categories>))
+                    .route("/{id}", web::get().to(find_category::<Categories>))
+                    .route("/{id}", web::put().to(put_category::<Categories>))
+                    .route("/{id}", web::delete().to(remove_category::<Categories>)),
+            )
     })
     .bind((host, port))?
     .run()
@@ -0,0 +1,218 @@
+use std::{
+    fmt,
+    future::{Ready, ready},
+    str::FromStr,
+};
+
+use actix_web::{
+    Error, FromRequest, HttpMessage, HttpRequest, ResponseError,
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::{
+        Method, StatusCode,
+        header::{AUTHORIZATION, HeaderValue, WWW_AUTHENTICATE},
+    },
+    middleware::Next,
+    web::Data,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+use crate::{handlers::api_error::ApiError, middleware::request_id::RequestId};
+
+/// Which requests need a bearer token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthScope {
+    Off,
+    /// Everything but `GET`, `HEAD` and `OPTIONS`.
+    Writes,
+    All,
+}
+impl FromStr for AuthScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "writes" => Ok(Self::Writes),
+            "all" => Ok(Self::All),
+            other => Err(format!(
+                "invalid auth scope '{}', expected off, writes or all",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthPolicy {
+    pub scope: AuthScope,
+    /// HS256 key tokens are signed with; required unless `scope` is `Off`.
+    pub jwt_secret: Option<String>,
+}
+impl fmt::Debug for AuthPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthPolicy")
+            .field("scope", &self.scope)
+            .field(
+                "jwt_secret",
+                &self.jwt_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Value of the `role` claim that lets a caller change and delete products.
+pub const ADMIN_ROLE: &str = "admin";
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    role: Option<String>,
+}
+
+/// Who a verified token was issued to. Put in the request extensions for
+/// handlers that want to record who made a change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthenticatedUser {
+    pub subject: String,
+    pub role: Option<String>,
+}
+impl AuthenticatedUser {
+    pub fn is_admin(&self) -> bool {
+        self.role.as_deref() == Some(ADMIN_ROLE)
+    }
+}
+
+/// Extractor for handlers only admins may call: 403 for callers whose token
+/// lacks the admin role, 401 for callers without one. When auth is off
+/// there's nobody to check, so everyone passes with `None`.
+pub struct AdminUser(pub Option<AuthenticatedUser>);
+impl FromRequest for AdminUser {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let auth_on = req
+            .app_data::<Data<Authenticator>>()
+            .is_some_and(|auth| auth.scope != AuthScope::Off);
+        let user = req.extensions().get::<AuthenticatedUser>().cloned();
+
+        ready(match user {
+            Some(user) if user.is_admin() => Ok(Self(Some(user))),
+            Some(_) => Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "requires the admin role",
+            )),
+            None if auth_on => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid bearer token",
+            )),
+            None => Ok(Self(None)),
+        })
+    }
+}
+
+fn is_read(method: &Method) -> bool {
+    [Method::GET, Method::HEAD, Method::OPTIONS].contains(method)
+}
+
+/// Verifies HS256 bearer tokens. Expired tokens are rejected, so every token
+/// must carry `exp`.
+pub struct Authenticator {
+    scope: AuthScope,
+    key: DecodingKey,
+    validation: Validation,
+}
+impl Authenticator {
+    pub fn new(policy: &AuthPolicy) -> Self {
+        let secret = policy.jwt_secret.as_deref().unwrap_or_default();
+        Self {
+            scope: policy.scope,
+            key: DecodingKey::from_secret(secret.as_bytes()),
+            validation: Validation::new(Algorithm::HS256),
+        }
+    }
+
+    fn requires_token(&self, method: &Method) -> bool {
+        match self.scope {
+            AuthScope::Off => false,
+            AuthScope::Writes => !is_read(method),
+            AuthScope::All => true,
+        }
+    }
+
+    fn verify(&self, req: &ServiceRequest) -> Option<AuthenticatedUser> {
+        let header = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+        let token = header
+            .strip_prefix("Bearer ")
+            .or_else(|| header.strip_prefix("bearer "))?;
+        let data = jsonwebtoken::decode::<Claims>(token.trim(), &self.key, &self.validation)
+            .inspect_err(|error| {
+                log::debug!(
+                    "request {}: rejected token: {}",
+                    RequestId::current(),
+                    error
+                )
+            })
+            .ok()?;
+
+        Some(AuthenticatedUser {
+            subject: data.claims.sub,
+            role: data.claims.role,
+        })
+    }
+}
+
+/// Answers 401 unless the request carries a valid bearer token, for the
+/// requests the app's `Data<Authenticator>` covers. Requests it doesn't cover
+/// still have a token checked if they send one, so extractors such as
+/// [`AdminUser`] see who's calling; a bad token there just counts as none.
+/// Lets everything through if no authenticator is registered or auth is off.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let authenticator = req.app_data::<Data<Authenticator>>().cloned();
+    let Some(authenticator) = authenticator.filter(|auth| auth.scope != AuthScope::Off) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+
+    if !authenticator.requires_token(req.method()) {
+        if let Some(user) = authenticator.verify(&req) {
+            req.extensions_mut().insert(user);
+        }
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    let Some(user) = authenticator.verify(&req) else {
+        let mut response =
+            ApiError::new(StatusCode::UNAUTHORIZED, "missing or invalid bearer token")
+                .error_response();
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return Ok(req.into_response(response).map_into_right_body());
+    };
+
+    if !is_read(req.method()) {
+        log::info!(
+            "request {}: {} {} by {}",
+            RequestId::current(),
+            req.method(),
+            req.path(),
+            user.subject
+        );
+    }
+    req.extensions_mut().insert(user);
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
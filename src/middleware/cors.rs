@@ -0,0 +1,63 @@
+use actix_cors::Cors;
+use actix_web::http::{
+    Method,
+    header::{ETAG, HeaderName, InvalidHeaderName, LOCATION},
+};
+
+use crate::{
+    handlers::product_handlers::{NEWEST_UPDATED_HEADER, OLDEST_CREATED_HEADER},
+    middleware::request_id::REQUEST_ID_HEADER,
+};
+
+/// Request headers clients send to this API beyond the CORS-safelisted ones.
+pub const DEFAULT_ALLOWED_HEADERS: &[&str] =
+    &["authorization", "content-type", "if-match", "x-request-id"];
+
+pub fn default_allowed_headers() -> Vec<HeaderName> {
+    DEFAULT_ALLOWED_HEADERS
+        .iter()
+        .map(|header| HeaderName::from_static(header))
+        .collect()
+}
+
+/// Parses a comma-separated header list such as `CORS_ALLOWED_HEADERS`.
+pub fn parse_header_list(list: &str) -> Result<Vec<HeaderName>, InvalidHeaderName> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+pub fn cors(allowed_headers: Vec<HeaderName>) -> Cors {
+    Cors::default()
+        .allow_any_origin()
+        .allowed_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allowed_headers(allowed_headers)
+        .expose_headers([
+            LOCATION.as_str(),
+            ETAG.as_str(),
+            OLDEST_CREATED_HEADER,
+            NEWEST_UPDATED_HEADER,
+            REQUEST_ID_HEADER,
+        ])
+        .max_age(3600)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_header_list() {
+        let headers = parse_header_list("If-Match, x-api-key,,").unwrap();
+        assert_eq!(headers, ["if-match", "x-api-key"]);
+        assert!(parse_header_list("bad header").is_err());
+    }
+}
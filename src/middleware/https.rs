@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use actix_web::{
+    Error, HttpResponse, ResponseError,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        StatusCode,
+        header::{HOST, LOCATION},
+    },
+    middleware::Next,
+};
+
+use crate::handlers::api_error::ApiError;
+
+pub const FORWARDED_PROTO_HEADER: &str = "X-Forwarded-Proto";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpsEnforcement {
+    Off,
+    Reject,
+    Redirect,
+}
+impl FromStr for HttpsEnforcement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "reject" => Ok(Self::Reject),
+            "redirect" => Ok(Self::Redirect),
+            other => Err(format!(
+                "invalid HTTPS enforcement mode '{}', expected off, reject or redirect",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HttpsPolicy {
+    pub enforcement: HttpsEnforcement,
+    /// Only a TLS-terminating proxy we control should be able to tell us the
+    /// original scheme, so `X-Forwarded-Proto` is ignored unless this is set.
+    pub trust_forwarded_proto: bool,
+}
+impl HttpsPolicy {
+    fn is_https(&self, req: &ServiceRequest) -> bool {
+        if self.trust_forwarded_proto
+            && let Some(proto) = req.headers().get(FORWARDED_PROTO_HEADER)
+        {
+            return proto
+                .to_str()
+                .is_ok_and(|proto| proto.trim().eq_ignore_ascii_case("https"));
+        }
+
+        req.app_config().secure()
+    }
+}
+
+pub async fn enforce_https(
+    policy: HttpsPolicy,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if policy.enforcement == HttpsEnforcement::Off || policy.is_https(&req) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    let response = match policy.enforcement {
+        HttpsEnforcement::Redirect => {
+            let host = req
+                .headers()
+                .get(HOST)
+                .and_then(|host| host.to_str().ok())
+                .unwrap_or(req.app_config().host())
+                .to_owned();
+            let path = req
+                .uri()
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or("/");
+
+            HttpResponse::PermanentRedirect()
+                .insert_header((LOCATION, format!("https://{}{}", host, path)))
+                .finish()
+        }
+        _ => ApiError::new(StatusCode::FORBIDDEN, "HTTPS is required.").error_response(),
+    };
+
+    Ok(req.into_response(response).map_into_right_body())
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web::Data,
+};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Label for requests that matched no route, so random paths can't each
+/// get their own series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct RouteKey {
+    method: String,
+    route: String,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Non-cumulative; `render` adds them up.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[i] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    requests: BTreeMap<(RouteKey, u16), u64>,
+    latencies: BTreeMap<RouteKey, Histogram>,
+}
+
+/// Request counts and latencies, labelled by method and route template
+/// (e.g. `/api/products/{id}`) rather than the concrete path.
+#[derive(Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+impl Metrics {
+    pub fn record(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let key = RouteKey {
+            method: method.to_owned(),
+            route: route.to_owned(),
+        };
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        *registry.requests.entry((key.clone(), status)).or_default() += 1;
+        registry
+            .latencies
+            .entry(key)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Requests handled, by route and status.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((key, status), count) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                escape(&key.method),
+                escape(&key.route),
+                status,
+                count
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds Time spent handling requests.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (key, histogram) in &registry.latencies {
+            let labels = format!(
+                "method=\"{}\",route=\"{}\"",
+                escape(&key.method),
+                escape(&key.route)
+            );
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+
+        out
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Records every request into the app's `Data<Metrics>`, if one is
+/// registered.
+pub async fn track_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(metrics) = req.app_data::<Data<Metrics>>().cloned() else {
+        return next.call(req).await;
+    };
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_owned());
+    let started = Instant::now();
+
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(res) => res.status(),
+        Err(error) => error.as_response_error().status_code(),
+    };
+    metrics.record(&method, &route, status.as_u16(), started.elapsed());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_and_cumulative_buckets() {
+        let metrics = Metrics::default();
+        metrics.record("GET", "/api/products/{id}", 200, Duration::from_millis(3));
+        metrics.record("GET", "/api/products/{id}", 200, Duration::from_millis(30));
+        metrics.record("GET", "/api/products/{id}", 404, Duration::from_secs(20));
+
+        let text = metrics.render();
+        let labels = "method=\"GET\",route=\"/api/products/{id}\"";
+        for line in [
+            format!("http_requests_total{{{},status=\"200\"}} 2", labels),
+            format!("http_requests_total{{{},status=\"404\"}} 1", labels),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1",
+                labels
+            ),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"0.05\"}} 2",
+                labels
+            ),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"10\"}} 2",
+                labels
+            ),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 3",
+                labels
+            ),
+            format!("http_request_duration_seconds_count{{{}}} 3", labels),
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {}", line);
+        }
+    }
+}
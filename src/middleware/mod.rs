@@ -0,0 +1,5 @@
+pub mod auth;
+pub mod cors;
+pub mod https;
+pub mod metrics;
+pub mod request_id;
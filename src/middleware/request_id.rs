@@ -0,0 +1,64 @@
+use actix_web::{
+    Error, HttpMessage,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// Longer incoming ids are replaced rather than echoed into logs.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// Identifies one request across log lines and services. Also available to
+/// handlers through the request extensions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(String);
+impl RequestId {
+    /// Keeps a well-formed id from the caller so it can be traced upstream,
+    /// otherwise makes up a new one.
+    fn from_header(value: Option<&HeaderValue>) -> Self {
+        value
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= MAX_REQUEST_ID_LENGTH
+                    && id.bytes().all(|b| b.is_ascii_graphic())
+            })
+            .map(|id| Self(id.to_owned()))
+            .unwrap_or_else(|| Self(Uuid::new_v4().to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The id of the request being handled, or `"-"` outside of one.
+    pub fn current() -> String {
+        CURRENT
+            .try_with(|id| id.0.clone())
+            .unwrap_or_else(|_| "-".to_owned())
+    }
+}
+
+/// Tags the request with an id, makes it visible to `RequestId::current` for
+/// the rest of the request, and echoes it back in `X-Request-Id`.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = RequestId::from_header(req.headers().get(REQUEST_ID_HEADER));
+    req.extensions_mut().insert(id.clone());
+
+    let header = HeaderValue::from_str(id.as_str()).expect("request ids are visible ASCII");
+    let mut res = CURRENT.scope(id, next.call(req)).await?;
+    res.headers_mut()
+        .insert(HeaderName::from_static("x-request-id"), header);
+    Ok(res)
+}
@@ -0,0 +1,560 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures_util::{Stream, stream};
+use uuid::Uuid;
+
+use crate::{
+    application::{
+        filtering::ProductFilter,
+        pagination::Page,
+        product_service::{ClassifyError, ProductRepository, Reservation},
+        sorting::Sort,
+    },
+    domain::{
+        category::CategoryId,
+        price::Price,
+        product::{NewProduct, PriceChange, Product, ProductId, ProductStats},
+        slug,
+    },
+};
+
+/// Keeps products in process memory, for tests and for running without a
+/// database. Filtering and sorting go through the same `matches`/`compare`
+/// the Postgres queries mirror.
+#[derive(Default)]
+pub struct InMemoryProductRepository {
+    products: Mutex<Vec<Product>>,
+    price_history: Mutex<Vec<(ProductId, PriceChange)>>,
+    fail: bool,
+}
+impl InMemoryProductRepository {
+    /// Makes every operation fail with `InMemoryError::Injected`, to exercise
+    /// error paths.
+    pub fn with_failures(mut self, fail: bool) -> Self {
+        self.fail = fail;
+        self
+    }
+
+    /// Stores `product` as is, replacing any with the same id. Lets tests set
+    /// up state the public operations can't produce, like old timestamps.
+    pub fn put(&self, product: Product) {
+        let mut products = self.products();
+        products.retain(|p| p.id != product.id);
+        products.push(product);
+    }
+
+    fn products(&self) -> MutexGuard<'_, Vec<Product>> {
+        self.products.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Copies of the products that aren't soft-deleted, or of all of them
+    /// with `include_deleted`.
+    fn visible(&self, include_deleted: bool) -> Vec<Product> {
+        self.products()
+            .iter()
+            .filter(|p| include_deleted || p.deleted_at.is_none())
+            .cloned()
+            .collect()
+    }
+
+    fn check(&self) -> Result<(), InMemoryError> {
+        if self.fail {
+            Err(InMemoryError::Injected)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Shared by `update` and `patch`.
+    fn update_fields(
+        &self,
+        id: ProductId,
+        changes: Changes,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, InMemoryError> {
+        self.check()?;
+        let Changes {
+            name,
+            description,
+            price,
+            tags,
+            sku,
+        } = changes;
+
+        let mut products = self.products();
+        let Some(index) = products
+            .iter()
+            .position(|p| p.id == id && p.deleted_at.is_none())
+        else {
+            return Ok(None);
+        };
+        if expected_version.is_some_and(|expected| expected != products[index].version) {
+            return Err(InMemoryError::StaleVersion);
+        }
+        let sku = match (&products[index].sku, sku) {
+            (Some(old), Some(sku)) if *old != sku => return Err(InMemoryError::SkuChanged),
+            (None, Some(sku)) if sku_taken(&products, &sku) => {
+                return Err(InMemoryError::Conflict);
+            }
+            (old, sku) => sku.or_else(|| old.clone()),
+        };
+
+        let p = &mut products[index];
+        let price = price.unwrap_or(p.price);
+        if p.price != price {
+            self.price_history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((
+                    id,
+                    PriceChange {
+                        old_price: p.price,
+                        new_price: price,
+                        changed_at: Utc::now(),
+                    },
+                ));
+        }
+        if let Some(name) = name {
+            p.name = name;
+        }
+        if let Some(description) = description {
+            p.description = description;
+        }
+        if let Some(tags) = tags {
+            p.tags = tags;
+        }
+        p.sku = sku;
+        p.price = price;
+        p.updated_at = Utc::now();
+        p.version += 1;
+        Ok(Some(p.clone()))
+    }
+}
+
+/// What `update` and `patch` write; a `None` field keeps its current value.
+struct Changes {
+    name: Option<String>,
+    description: Option<String>,
+    price: Option<Price>,
+    tags: Option<Vec<String>>,
+    /// Only set if the product has none yet.
+    sku: Option<String>,
+}
+
+/// Whether a live product has `sku`; deleted ones give theirs up.
+fn sku_taken(products: &[Product], sku: &str) -> bool {
+    products
+        .iter()
+        .any(|p| p.deleted_at.is_none() && p.sku.as_deref() == Some(sku))
+}
+
+/// Adds a new product with a slug no other product has, returning a copy.
+fn insert(
+    products: &mut Vec<Product>,
+    name: String,
+    description: String,
+    price: Price,
+    tags: Vec<String>,
+    sku: Option<String>,
+) -> Product {
+    let taken: Vec<String> = products.iter().map(|p| p.slug.clone()).collect();
+    let slug = slug::with_unique_suffix(&slug::slugify(&name), taken.iter().map(String::as_str));
+    let now = Utc::now();
+    let product = Product {
+        id: Uuid::new_v4().into(),
+        name,
+        slug,
+        sku,
+        description,
+        price,
+        created_at: now,
+        updated_at: now,
+        version: 1,
+        deleted_at: None,
+        category_id: None,
+        tags,
+        stock: 0,
+    };
+
+    products.push(product.clone());
+    product
+}
+
+#[derive(Debug)]
+pub enum InMemoryError {
+    /// Set up with `with_failures`.
+    Injected,
+    /// Another product already has the SKU.
+    Conflict,
+    StaleVersion,
+    /// An update tried to replace the product's SKU.
+    SkuChanged,
+}
+impl fmt::Display for InMemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Injected => write!(f, "in-memory repository failure"),
+            Self::Conflict => write!(f, "sku is taken"),
+            Self::StaleVersion => write!(f, "product is no longer at the expected version"),
+            Self::SkuChanged => write!(f, "product already has another sku"),
+        }
+    }
+}
+impl Error for InMemoryError {}
+impl ClassifyError for InMemoryError {
+    fn is_conflict(&self) -> bool {
+        matches!(self, Self::Conflict)
+    }
+
+    fn is_stale(&self) -> bool {
+        matches!(self, Self::StaleVersion)
+    }
+
+    fn is_immutable(&self) -> bool {
+        matches!(self, Self::SkuChanged)
+    }
+}
+
+impl ProductRepository for InMemoryProductRepository {
+    type Error = InMemoryError;
+
+    async fn create(
+        &self,
+        name: String,
+        description: String,
+        price: Price,
+        tags: Vec<String>,
+        sku: Option<String>,
+    ) -> Result<Product, Self::Error> {
+        self.check()?;
+
+        let mut products = self.products();
+        if sku.as_deref().is_some_and(|sku| sku_taken(&products, sku)) {
+            return Err(InMemoryError::Conflict);
+        }
+        Ok(insert(&mut products, name, description, price, tags, sku))
+    }
+
+    async fn create_many(&self, products: Vec<NewProduct>) -> Result<Vec<Product>, Self::Error> {
+        self.check()?;
+
+        // One lock for the lot, so no one sees a partial import.
+        let mut stored = self.products();
+        let mut skus: Vec<_> = stored
+            .iter()
+            .filter(|p| p.deleted_at.is_none())
+            .filter_map(|p| p.sku.as_ref())
+            .collect();
+        for sku in products.iter().filter_map(|p| p.sku.as_ref()) {
+            if skus.contains(&sku) {
+                return Err(InMemoryError::Conflict);
+            }
+            skus.push(sku);
+        }
+
+        Ok(products
+            .into_iter()
+            .map(|p| insert(&mut stored, p.name, p.description, p.price, p.tags, p.sku))
+            .collect())
+    }
+
+    async fn read_all(&self) -> Result<Vec<Product>, Self::Error> {
+        self.check()?;
+        Ok(self.visible(false))
+    }
+
+    fn stream_all(&self) -> impl Stream<Item = Result<Product, Self::Error>> + Send + '_ {
+        let products = match self.check() {
+            Ok(()) => {
+                let mut products = self.visible(false);
+                products.sort_by_key(|p| p.created_at);
+                products.into_iter().map(Ok).collect()
+            }
+            Err(error) => vec![Err(error)],
+        };
+        stream::iter(products)
+    }
+
+    async fn read_sorted(
+        &self,
+        filter: &ProductFilter,
+        sort: Sort,
+        page: Page,
+        include_deleted: bool,
+    ) -> Result<Vec<Product>, Self::Error> {
+        self.check()?;
+
+        let mut products: Vec<_> = self
+            .visible(include_deleted)
+            .into_iter()
+            .filter(|p| filter.matches(p))
+            .filter(|p| {
+                page.after
+                    .is_none_or(|after| (p.updated_at, p.id) < (after.updated_at, after.id))
+            })
+            .collect();
+        products.sort_by(|a, b| sort.compare(a, b));
+        Ok(products
+            .into_iter()
+            .skip(page.offset as usize)
+            .take(page.limit as usize)
+            .collect())
+    }
+
+    fn stream_sorted<'a>(
+        &'a self,
+        filter: &'a ProductFilter,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> impl Stream<Item = Result<Product, Self::Error>> + Send + 'a {
+        let products = match self.check() {
+            Ok(()) => {
+                let mut products: Vec<_> = self
+                    .visible(include_deleted)
+                    .into_iter()
+                    .filter(|p| filter.matches(p))
+                    .collect();
+                products.sort_by(|a, b| sort.compare(a, b));
+                products.into_iter().map(Ok).collect()
+            }
+            Err(error) => vec![Err(error)],
+        };
+        stream::iter(products)
+    }
+
+    async fn count(
+        &self,
+        filter: &ProductFilter,
+        include_deleted: bool,
+    ) -> Result<u64, Self::Error> {
+        self.check()?;
+        Ok(self
+            .visible(include_deleted)
+            .iter()
+            .filter(|p| filter.matches(p))
+            .count() as u64)
+    }
+
+    async fn stats(&self) -> Result<ProductStats, Self::Error> {
+        self.check()?;
+
+        let prices: Vec<u64> = self
+            .visible(false)
+            .iter()
+            .map(|p| p.price.cents().into())
+            .collect();
+        let count = prices.len() as u64;
+        // Halves round up, like Postgres' `round` does for positive numbers.
+        let average = (count > 0).then(|| (prices.iter().sum::<u64>() + count / 2) / count);
+        let price = |cents: Option<u64>| cents.and_then(|cents| Price::new(cents as u32).ok());
+
+        Ok(ProductStats {
+            count,
+            min_price: price(prices.iter().copied().min()),
+            max_price: price(prices.iter().copied().max()),
+            average_price: price(average),
+        })
+    }
+
+    async fn read_one(
+        &self,
+        id: ProductId,
+        include_deleted: bool,
+    ) -> Result<Option<Product>, Self::Error> {
+        self.check()?;
+        Ok(self
+            .visible(include_deleted)
+            .into_iter()
+            .find(|p| p.id == id))
+    }
+
+    async fn read_by_slug(&self, slug: &str) -> Result<Option<Product>, Self::Error> {
+        self.check()?;
+        Ok(self.visible(false).into_iter().find(|p| p.slug == slug))
+    }
+
+    /// Soft-deleted products keep their SKU, as in Postgres, but aren't
+    /// found by it.
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, Self::Error> {
+        self.check()?;
+        Ok(self
+            .visible(false)
+            .into_iter()
+            .find(|p| p.sku.as_deref() == Some(sku)))
+    }
+
+    async fn read_updated_within(&self, within: Duration) -> Result<Vec<Product>, Self::Error> {
+        self.check()?;
+
+        let since = Utc::now() - within;
+        Ok(self
+            .visible(false)
+            .into_iter()
+            .filter(|p| p.updated_at > since)
+            .collect())
+    }
+
+    async fn update(
+        &self,
+        id: ProductId,
+        product: NewProduct,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, Self::Error> {
+        let changes = Changes {
+            name: Some(product.name),
+            description: Some(product.description),
+            price: Some(product.price),
+            tags: Some(product.tags),
+            sku: product.sku,
+        };
+        self.update_fields(id, changes, expected_version)
+    }
+
+    async fn patch(
+        &self,
+        id: ProductId,
+        name: Option<String>,
+        description: Option<String>,
+        price: Option<Price>,
+        tags: Option<Vec<String>>,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, Self::Error> {
+        let changes = Changes {
+            name,
+            description,
+            price,
+            tags,
+            sku: None,
+        };
+        self.update_fields(id, changes, expected_version)
+    }
+
+    async fn delete(&self, id: ProductId) -> Result<bool, Self::Error> {
+        self.check()?;
+
+        let mut products = self.products();
+        let Some(p) = products
+            .iter_mut()
+            .find(|p| p.id == id && p.deleted_at.is_none())
+        else {
+            return Ok(false);
+        };
+
+        p.deleted_at = Some(Utc::now());
+        Ok(true)
+    }
+
+    async fn delete_many(&self, ids: &[ProductId]) -> Result<u64, Self::Error> {
+        self.check()?;
+
+        let now = Utc::now();
+        let mut deleted = 0;
+        for p in self.products().iter_mut() {
+            if p.deleted_at.is_none() && ids.contains(&p.id) {
+                p.deleted_at = Some(now);
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn restore(&self, id: ProductId) -> Result<Option<Product>, Self::Error> {
+        self.check()?;
+
+        let mut products = self.products();
+        let Some(index) = products
+            .iter()
+            .position(|p| p.id == id && p.deleted_at.is_some())
+        else {
+            return Ok(None);
+        };
+        if let Some(sku) = &products[index].sku
+            && sku_taken(&products, sku)
+        {
+            return Err(InMemoryError::Conflict);
+        }
+
+        let p = &mut products[index];
+        p.deleted_at = None;
+        p.updated_at = Utc::now();
+        p.version += 1;
+        Ok(Some(p.clone()))
+    }
+
+    async fn set_category(
+        &self,
+        id: ProductId,
+        category_id: Option<CategoryId>,
+    ) -> Result<Option<Product>, Self::Error> {
+        self.check()?;
+
+        let mut products = self.products();
+        let Some(p) = products
+            .iter_mut()
+            .find(|p| p.id == id && p.deleted_at.is_none())
+        else {
+            return Ok(None);
+        };
+
+        p.category_id = category_id;
+        p.updated_at = Utc::now();
+        p.version += 1;
+        Ok(Some(p.clone()))
+    }
+
+    async fn set_stock(&self, id: ProductId, stock: u32) -> Result<Option<Product>, Self::Error> {
+        self.check()?;
+
+        let mut products = self.products();
+        let Some(p) = products
+            .iter_mut()
+            .find(|p| p.id == id && p.deleted_at.is_none())
+        else {
+            return Ok(None);
+        };
+
+        p.stock = stock;
+        p.updated_at = Utc::now();
+        p.version += 1;
+        Ok(Some(p.clone()))
+    }
+
+    async fn reserve(&self, id: ProductId, quantity: u32) -> Result<Reservation, Self::Error> {
+        self.check()?;
+
+        let mut products = self.products();
+        let Some(p) = products
+            .iter_mut()
+            .find(|p| p.id == id && p.deleted_at.is_none())
+        else {
+            return Ok(Reservation::NotFound);
+        };
+        if p.stock < quantity {
+            return Ok(Reservation::Insufficient { available: p.stock });
+        }
+
+        p.stock -= quantity;
+        p.updated_at = Utc::now();
+        p.version += 1;
+        Ok(Reservation::Reserved(Box::new(p.clone())))
+    }
+
+    async fn read_price_history(&self, id: ProductId) -> Result<Vec<PriceChange>, Self::Error> {
+        self.check()?;
+
+        Ok(self
+            .price_history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|(product_id, _)| *product_id == id)
+            .map(|(_, change)| change.clone())
+            .collect())
+    }
+}
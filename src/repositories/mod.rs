@@ -1 +1,4 @@
+pub mod category_repository;
+#[cfg(any(test, feature = "memory"))]
+pub mod memory_product_repository;
 pub mod product_repository;
@@ -1,104 +1,811 @@
+use std::{error::Error, fmt, time::Duration};
+
+use async_stream::try_stream;
 use chrono::{DateTime, Utc};
-use sqlx::{PgPool, prelude::FromRow};
-use uuid::Uuid;
+use futures_util::{Stream, StreamExt, TryStreamExt};
+use sqlx::{
+    PgExecutor, PgPool, Postgres, QueryBuilder, postgres::PgConnectOptions, prelude::FromRow,
+};
+
+use crate::{
+    application::{
+        filtering::ProductFilter,
+        pagination::{Cursor, Page},
+        product_service::{ClassifyError, ProductRepository, Reservation},
+        sorting::{Order, Sort},
+    },
+    domain::{
+        category::CategoryId,
+        price::Price,
+        product::{NewProduct, PriceChange, Product, ProductId, ProductStats},
+        slug,
+    },
+};
 
-use crate::{application::product_service::ProductRepository, domain::product::Product};
+const SLUG_INDEX: &str = "products_slug_idx";
+/// SQLSTATE `query_canceled`, raised when `statement_timeout` fires.
+const QUERY_CANCELED: &str = "57014";
+/// SQLSTATE `unique_violation`.
+const UNIQUE_VIOLATION: &str = "23505";
+/// SQLSTATE `foreign_key_violation`.
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+/// How many times a write is retried when a concurrent write grabs the slug
+/// we picked between the lookup and the insert.
+const SLUG_ATTEMPTS: u32 = 3;
 
 #[derive(FromRow)]
 struct PgProductModel {
-    id: Uuid,
+    id: ProductId,
     name: String,
+    slug: String,
+    sku: Option<String>,
     description: String,
-    price: i32,
-    #[allow(dead_code)]
+    #[sqlx(try_from = "i32")]
+    price: Price,
     created_at: DateTime<Utc>,
-    #[allow(dead_code)]
     updated_at: DateTime<Utc>,
+    version: i32,
+    deleted_at: Option<DateTime<Utc>>,
+    category_id: Option<CategoryId>,
+    tags: Vec<String>,
+    #[sqlx(try_from = "i32")]
+    stock: u32,
 }
 impl From<PgProductModel> for Product {
     fn from(value: PgProductModel) -> Self {
         Self {
             id: value.id,
             name: value.name,
+            slug: value.slug,
+            sku: value.sku,
             description: value.description,
-            price: value.price as u32,
+            price: value.price,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            version: value.version,
+            deleted_at: value.deleted_at,
+            category_id: value.category_id,
+            tags: value.tags,
+            stock: value.stock,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct PgStatsModel {
+    count: i64,
+    min_price: Option<i32>,
+    max_price: Option<i32>,
+    average_price: Option<i32>,
+}
+impl TryFrom<PgStatsModel> for ProductStats {
+    type Error = sqlx::Error;
+
+    fn try_from(value: PgStatsModel) -> Result<Self, Self::Error> {
+        let price = |cents: Option<i32>| {
+            cents
+                .map(Price::try_from)
+                .transpose()
+                .map_err(|error| sqlx::Error::Decode(Box::new(error)))
+        };
+
+        Ok(Self {
+            count: value.count as u64,
+            min_price: price(value.min_price)?,
+            max_price: price(value.max_price)?,
+            average_price: price(value.average_price)?,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct PgPriceChangeModel {
+    #[sqlx(try_from = "i32")]
+    old_price: Price,
+    #[sqlx(try_from = "i32")]
+    new_price: Price,
+    changed_at: DateTime<Utc>,
+}
+impl From<PgPriceChangeModel> for PriceChange {
+    fn from(value: PgPriceChangeModel) -> Self {
+        Self {
+            old_price: value.old_price,
+            new_price: value.new_price,
+            changed_at: value.changed_at,
+        }
+    }
+}
+
+/// Has Postgres cancel any statement running longer than `timeout`, so a
+/// runaway query can't hold on to a pooled connection.
+pub fn with_statement_timeout(options: PgConnectOptions, timeout: Duration) -> PgConnectOptions {
+    options.options([("statement_timeout", format!("{}ms", timeout.as_millis()))])
+}
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    Sqlx(sqlx::Error),
+    Timeout(sqlx::Error),
+    /// A unique constraint rejected the write.
+    Conflict(sqlx::Error),
+    /// The write pointed at a missing row, or deleted one still pointed at.
+    ForeignKey(sqlx::Error),
+    /// A conditional write found the product at another version.
+    StaleVersion,
+    /// An update tried to replace the product's SKU.
+    SkuChanged,
+    TooManyRows {
+        limit: u32,
+    },
+}
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlx(error) => write!(f, "{}", error),
+            Self::Timeout(error) => write!(f, "statement timed out: {}", error),
+            Self::Conflict(error) => write!(f, "unique constraint violated: {}", error),
+            Self::ForeignKey(error) => write!(f, "foreign key violated: {}", error),
+            Self::StaleVersion => write!(f, "product is no longer at the expected version"),
+            Self::SkuChanged => write!(f, "product already has another sku"),
+            Self::TooManyRows { limit } => {
+                write!(f, "query would return more than {} rows", limit)
+            }
+        }
+    }
+}
+impl Error for RepositoryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Sqlx(error)
+            | Self::Timeout(error)
+            | Self::Conflict(error)
+            | Self::ForeignKey(error) => Some(error),
+            Self::StaleVersion | Self::SkuChanged | Self::TooManyRows { .. } => None,
+        }
+    }
+}
+impl ClassifyError for RepositoryError {
+    fn is_timeout(&self) -> bool {
+        matches!(self, Self::Timeout(_))
+    }
+
+    fn is_conflict(&self) -> bool {
+        matches!(self, Self::Conflict(_) | Self::ForeignKey(_))
+    }
+
+    fn is_stale(&self) -> bool {
+        matches!(self, Self::StaleVersion)
+    }
+
+    fn is_immutable(&self) -> bool {
+        matches!(self, Self::SkuChanged)
+    }
+}
+impl From<sqlx::Error> for RepositoryError {
+    fn from(value: sqlx::Error) -> Self {
+        let code = value
+            .as_database_error()
+            .and_then(|error| error.code())
+            .map(|code| code.into_owned());
+        match code.as_deref() {
+            Some(QUERY_CANCELED) => Self::Timeout(value),
+            Some(UNIQUE_VIOLATION) => Self::Conflict(value),
+            Some(FOREIGN_KEY_VIOLATION) => Self::ForeignKey(value),
+            _ => Self::Sqlx(value),
+        }
+    }
+}
+
+fn is_slug_conflict(error: &sqlx::Error) -> bool {
+    matches!(error, sqlx::Error::Database(error) if error.constraint() == Some(SLUG_INDEX))
+}
+
+/// Makes `%`, `_` and `\` match themselves in a `LIKE` pattern.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Every product matching `filter`, in `sort` order, ready for a `LIMIT`.
+/// With `after`, only those past that cursor, which assumes the default sort.
+fn select_sorted<'a>(
+    filter: &ProductFilter,
+    sort: Sort,
+    include_deleted: bool,
+    after: Option<Cursor>,
+) -> QueryBuilder<'a, Postgres> {
+    // Field names come from the static registry, never from the raw query
+    // parameter.
+    let column = sort.field.name;
+    let order = match sort.order {
+        Order::Asc => "ASC",
+        Order::Desc => "DESC",
+    };
+
+    let mut query = QueryBuilder::new("SELECT * FROM products");
+    let keyword = push_filter(&mut query, filter, include_deleted);
+    if let Some(after) = after {
+        // A row comparison, so the `(updated_at, id)` index can seek to it.
+        query
+            .push(keyword)
+            .push("(updated_at, id) < (")
+            .push_bind(after.updated_at)
+            .push(", ")
+            .push_bind(after.id)
+            .push(")");
+    }
+    query.push(format_args!(" ORDER BY {} {}, id {}", column, order, order));
+    query
+}
+
+/// Appends a `WHERE` clause constraining only what `filter` sets, and
+/// skipping deleted products unless `include_deleted` is set. Returns the
+/// keyword a further condition should start with.
+fn push_filter(
+    query: &mut QueryBuilder<'_, Postgres>,
+    filter: &ProductFilter,
+    include_deleted: bool,
+) -> &'static str {
+    let mut keyword = " WHERE ";
+    if !include_deleted {
+        query.push(keyword).push("deleted_at IS NULL");
+        keyword = " AND ";
+    }
+    if let Some(category_id) = filter.category_id {
+        query
+            .push(keyword)
+            .push("category_id = ")
+            .push_bind(category_id);
+        keyword = " AND ";
+    }
+    if let Some(tag) = &filter.tag {
+        query
+            .push(keyword)
+            .push_bind(tag.clone())
+            .push(" = ANY(tags)");
+        keyword = " AND ";
+    }
+    if let Some(search) = &filter.search {
+        query
+            .push(keyword)
+            .push("name ILIKE '%' || ")
+            .push_bind(escape_like(search))
+            .push(" || '%'");
+        keyword = " AND ";
+    }
+    // Field names come from the static registry, so they're safe to splice in.
+    for range in &filter.ranges {
+        for (operator, bound) in [(">=", range.min), ("<=", range.max)] {
+            if let Some(bound) = bound {
+                query
+                    .push(keyword)
+                    .push(format_args!("{} {} ", range.field.name, operator))
+                    .push_bind(bound);
+                keyword = " AND ";
+            }
         }
     }
+    keyword
+}
+
+/// What `update` and `patch` write; a `None` field keeps its current value.
+#[derive(Clone, Copy)]
+struct Changes<'a> {
+    name: Option<&'a str>,
+    description: Option<&'a str>,
+    price: Option<Price>,
+    tags: Option<&'a [String]>,
+    /// Only set if the product has none yet.
+    sku: Option<&'a str>,
+}
+
+/// Picks the first free slug for `base`, ignoring the product being renamed.
+async fn free_slug<'c>(
+    executor: impl PgExecutor<'c>,
+    base: &str,
+    exclude: Option<ProductId>,
+) -> Result<String, sqlx::Error> {
+    let taken: Vec<String> = sqlx::query_scalar(
+        "SELECT slug FROM products WHERE (slug = $1 OR slug LIKE $1 || '-%') AND ($2::uuid IS NULL OR id <> $2)",
+    )
+    .bind(base)
+    .bind(exclude)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(slug::with_unique_suffix(
+        base,
+        taken.iter().map(String::as_str),
+    ))
 }
 
 pub struct PgProductRepository {
     pool: PgPool,
+    max_rows: u32,
+    regenerate_slugs: bool,
+    touch_on_restore: bool,
 }
 impl PgProductRepository {
+    pub const DEFAULT_MAX_ROWS: u32 = 10_000;
+
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            max_rows: Self::DEFAULT_MAX_ROWS,
+            regenerate_slugs: false,
+            touch_on_restore: true,
+        }
+    }
+
+    /// Caps how many rows the non-streaming list queries may materialize.
+    pub fn with_max_rows(mut self, max_rows: u32) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Whether renaming a product also gives it a new slug. Off by default so
+    /// existing links keep working.
+    pub fn with_slug_regeneration(mut self, regenerate_slugs: bool) -> Self {
+        self.regenerate_slugs = regenerate_slugs;
+        self
+    }
+
+    /// Whether restoring a product bumps its `updated_at`, so sync clients
+    /// notice it reappearing. On by default; off treats a restore as not
+    /// modifying the product.
+    pub fn with_restore_touch(mut self, touch_on_restore: bool) -> Self {
+        self.touch_on_restore = touch_on_restore;
+        self
+    }
+
+    /// Queries are run with `LIMIT max_rows + 1`, so getting that extra row
+    /// back means the real result set is over the ceiling.
+    fn limit_probe(&self) -> i64 {
+        self.max_rows as i64 + 1
+    }
+
+    fn check_row_limit(
+        &self,
+        models: Vec<PgProductModel>,
+    ) -> Result<Vec<Product>, RepositoryError> {
+        if models.len() > self.max_rows as usize {
+            return Err(RepositoryError::TooManyRows {
+                limit: self.max_rows,
+            });
+        }
+
+        Ok(models.into_iter().map(|model| model.into()).collect())
+    }
+
+    /// Shared by `update` and `patch`, retried while concurrent writes take
+    /// the new slug.
+    async fn update_fields(
+        &self,
+        id: ProductId,
+        changes: Changes<'_>,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, RepositoryError> {
+        let mut attempt = 1;
+        loop {
+            let result = self.try_update(id, changes, expected_version).await;
+            match result {
+                Err(RepositoryError::Conflict(error))
+                    if attempt < SLUG_ATTEMPTS && is_slug_conflict(&error) =>
+                {
+                    attempt += 1
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn try_update(
+        &self,
+        id: ProductId,
+        changes: Changes<'_>,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, RepositoryError> {
+        let Changes {
+            name,
+            description,
+            price,
+            tags,
+            sku,
+        } = changes;
+        let mut tx = self.pool.begin().await?;
+
+        // Lock the row so the recorded old price, and the SKU checked here,
+        // can't be changed underneath us.
+        let old: Option<(i32, String, Option<String>)> = sqlx::query_as(
+            "SELECT price, name, sku FROM products WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some((old_price, old_name, old_sku)) = old else {
+            return Ok(None);
+        };
+        if let (Some(old_sku), Some(sku)) = (&old_sku, sku)
+            && old_sku != sku
+        {
+            return Err(RepositoryError::SkuChanged);
+        }
+
+        let slug = match name {
+            Some(name) if self.regenerate_slugs && old_name != name => {
+                Some(free_slug(&mut *tx, &slug::slugify(name), Some(id)).await?)
+            }
+            _ => None,
+        };
+
+        // The row exists and is locked, so matching nothing means the version
+        // didn't.
+        let model = sqlx::query_as::<_, PgProductModel>(
+            "UPDATE products SET name=COALESCE($1, name), slug=COALESCE($2, slug), description=COALESCE($3, description), price=COALESCE($4, price), tags=COALESCE($7, tags), sku=COALESCE(sku, $8), updated_at=now(), version=version + 1 WHERE id=$5 AND ($6::int IS NULL OR version=$6) RETURNING *",
+        )
+        .bind(name)
+        .bind(slug)
+        .bind(description)
+        .bind(price.map(i32::from))
+        .bind(id)
+        .bind(expected_version)
+        .bind(tags)
+        .bind(sku)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(model) = model else {
+            return Err(RepositoryError::StaleVersion);
+        };
+
+        if i32::from(model.price) != old_price {
+            // `now()` is when the transaction started, which may be before an
+            // earlier change got the row lock; stamp it with the time under
+            // the lock instead so the history reads back in order.
+            sqlx::query(
+                "INSERT INTO product_price_history (product_id, old_price, new_price, changed_at) VALUES ($1, $2, $3, clock_timestamp())",
+            )
+            .bind(id)
+            .bind(old_price)
+            .bind(i32::from(model.price))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(Some(model.into()))
     }
 }
 impl ProductRepository for PgProductRepository {
-    type Error = sqlx::Error;
+    type Error = RepositoryError;
 
     async fn create(
         &self,
         name: String,
         description: String,
-        price: u32,
+        price: Price,
+        tags: Vec<String>,
+        sku: Option<String>,
     ) -> Result<Product, Self::Error> {
+        let base = slug::slugify(&name);
+        let mut attempt = 1;
+        loop {
+            let slug = free_slug(&self.pool, &base, None).await?;
+            let result = sqlx::query_as::<_, PgProductModel>(
+                "INSERT INTO products (name, slug, description, price, tags, sku) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+            )
+            .bind(&name)
+            .bind(slug)
+            .bind(&description)
+            .bind(i32::from(price))
+            .bind(&tags)
+            .bind(&sku)
+            .fetch_one(&self.pool)
+            .await;
+
+            match result {
+                Err(error) if attempt < SLUG_ATTEMPTS && is_slug_conflict(&error) => attempt += 1,
+                result => return result.map(|model| model.into()).map_err(Into::into),
+            }
+        }
+    }
+
+    /// Slugs are picked inside the transaction, so rows in the same import
+    /// don't collide with each other. A concurrent write taking one of them
+    /// fails the whole import rather than being retried.
+    async fn create_many(&self, products: Vec<NewProduct>) -> Result<Vec<Product>, Self::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut created = Vec::with_capacity(products.len());
+        for product in products {
+            let slug = free_slug(&mut *tx, &slug::slugify(&product.name), None).await?;
+            let model = sqlx::query_as::<_, PgProductModel>(
+                "INSERT INTO products (name, slug, description, price, tags, sku) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+            )
+            .bind(product.name)
+            .bind(slug)
+            .bind(product.description)
+            .bind(i32::from(product.price))
+            .bind(product.tags)
+            .bind(product.sku)
+            .fetch_one(&mut *tx)
+            .await?;
+            created.push(model.into());
+        }
+
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    async fn read_all(&self) -> Result<Vec<Product>, Self::Error> {
+        let models = sqlx::query_as::<_, PgProductModel>(
+            "SELECT * FROM products WHERE deleted_at IS NULL ORDER BY updated_at DESC LIMIT $1",
+        )
+        .bind(self.limit_probe())
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.check_row_limit(models)
+    }
+
+    fn stream_all(&self) -> impl Stream<Item = Result<Product, Self::Error>> + Send + '_ {
         sqlx::query_as::<_, PgProductModel>(
-            "INSERT INTO products (name, description, price) VALUES ($1, $2, $3) RETURNING *",
+            "SELECT * FROM products WHERE deleted_at IS NULL ORDER BY created_at, id",
         )
-        .bind(name)
-        .bind(description)
-        .bind(price as i32)
-        .fetch_one(&self.pool)
-        .await
-        .map(|model| model.into())
+        .fetch(&self.pool)
+        .map(|row| row.map(|model| model.into()).map_err(Into::into))
     }
 
-    async fn read_all(&self) -> Result<Vec<Product>, Self::Error> {
-        sqlx::query_as::<_, PgProductModel>("SELECT * FROM products ORDER BY updated_at DESC")
+    async fn read_sorted(
+        &self,
+        filter: &ProductFilter,
+        sort: Sort,
+        page: Page,
+        include_deleted: bool,
+    ) -> Result<Vec<Product>, Self::Error> {
+        let mut query = select_sorted(filter, sort, include_deleted, page.after);
+        query
+            .push(" LIMIT ")
+            .push_bind((page.limit as i64).min(self.limit_probe()))
+            .push(" OFFSET ")
+            .push_bind(page.offset as i64);
+
+        let models = query
+            .build_query_as::<PgProductModel>()
             .fetch_all(&self.pool)
-            .await
-            .map(|vec| vec.into_iter().map(|model| model.into()).collect())
+            .await?;
+
+        self.check_row_limit(models)
     }
 
-    async fn read_one(&self, id: Uuid) -> Result<Option<Product>, Self::Error> {
-        sqlx::query_as::<_, PgProductModel>("SELECT * FROM products WHERE id = $1")
-            .bind(id)
-            .fetch_optional(&self.pool)
+    fn stream_sorted<'a>(
+        &'a self,
+        filter: &'a ProductFilter,
+        sort: Sort,
+        include_deleted: bool,
+    ) -> impl Stream<Item = Result<Product, Self::Error>> + Send + 'a {
+        try_stream! {
+            let mut query = select_sorted(filter, sort, include_deleted, None);
+            let mut rows = query.build_query_as::<PgProductModel>().fetch(&self.pool);
+            while let Some(model) = rows.try_next().await? {
+                yield model.into();
+            }
+        }
+    }
+
+    async fn count(
+        &self,
+        filter: &ProductFilter,
+        include_deleted: bool,
+    ) -> Result<u64, Self::Error> {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) FROM products");
+        push_filter(&mut query, filter, include_deleted);
+
+        query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.pool)
             .await
-            .map(|opt| opt.map(|model| model.into()))
+            .map(|count| count as u64)
+            .map_err(Into::into)
     }
 
-    async fn update(
+    /// One pass over the table; the aggregates are `NULL`, and so `None`,
+    /// when it's empty.
+    async fn stats(&self) -> Result<ProductStats, Self::Error> {
+        let model = sqlx::query_as::<_, PgStatsModel>(
+            "SELECT count(*) AS count, min(price) AS min_price, max(price) AS max_price, round(avg(price))::int AS average_price FROM products WHERE deleted_at IS NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(model.try_into()?)
+    }
+
+    async fn read_one(
         &self,
-        id: Uuid,
-        name: String,
-        description: String,
-        price: u32,
+        id: ProductId,
+        include_deleted: bool,
     ) -> Result<Option<Product>, Self::Error> {
         sqlx::query_as::<_, PgProductModel>(
-            "UPDATE products SET name=$1, description=$2, price=$3, updated_at=now() WHERE id=$4 RETURNING *",
+            "SELECT * FROM products WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
         )
-        .bind(name)
-        .bind(description)
-        .bind(price as i32)
         .bind(id)
+        .bind(include_deleted)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|opt| opt.map(|model| model.into()))
+        .map_err(Into::into)
+    }
+
+    async fn read_by_slug(&self, slug: &str) -> Result<Option<Product>, Self::Error> {
+        sqlx::query_as::<_, PgProductModel>(
+            "SELECT * FROM products WHERE slug = $1 AND deleted_at IS NULL",
+        )
+        .bind(slug)
         .fetch_optional(&self.pool)
         .await
         .map(|opt| opt.map(|model| model.into()))
+        .map_err(Into::into)
+    }
+
+    async fn find_by_sku(&self, sku: &str) -> Result<Option<Product>, Self::Error> {
+        sqlx::query_as::<_, PgProductModel>(
+            "SELECT * FROM products WHERE sku = $1 AND deleted_at IS NULL",
+        )
+        .bind(sku)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|opt| opt.map(|model| model.into()))
+        .map_err(Into::into)
+    }
+
+    async fn read_updated_within(&self, within: Duration) -> Result<Vec<Product>, Self::Error> {
+        let models = sqlx::query_as::<_, PgProductModel>(
+            "SELECT * FROM products WHERE deleted_at IS NULL AND updated_at > now() - $1 ORDER BY updated_at DESC LIMIT $2",
+        )
+        .bind(within)
+        .bind(self.limit_probe())
+        .fetch_all(&self.pool)
+        .await?;
+
+        self.check_row_limit(models)
     }
 
-    async fn delete(&self, id: Uuid) -> Result<bool, Self::Error> {
-        sqlx::query("DELETE FROM products WHERE id = $1")
+    async fn update(
+        &self,
+        id: ProductId,
+        product: NewProduct,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, Self::Error> {
+        let changes = Changes {
+            name: Some(&product.name),
+            description: Some(&product.description),
+            price: Some(product.price),
+            tags: Some(&product.tags),
+            sku: product.sku.as_deref(),
+        };
+        self.update_fields(id, changes, expected_version).await
+    }
+
+    async fn patch(
+        &self,
+        id: ProductId,
+        name: Option<String>,
+        description: Option<String>,
+        price: Option<Price>,
+        tags: Option<Vec<String>>,
+        expected_version: Option<i32>,
+    ) -> Result<Option<Product>, Self::Error> {
+        let changes = Changes {
+            name: name.as_deref(),
+            description: description.as_deref(),
+            price,
+            tags: tags.as_deref(),
+            sku: None,
+        };
+        self.update_fields(id, changes, expected_version).await
+    }
+
+    async fn delete(&self, id: ProductId) -> Result<bool, Self::Error> {
+        sqlx::query("UPDATE products SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL")
             .bind(id)
             .execute(&self.pool)
             .await
-            .map(|res| {
-                if res.rows_affected() == 0 {
-                    false
-                } else {
-                    true
-                }
-            })
+            .map(|res| res.rows_affected() != 0)
+            .map_err(Into::into)
+    }
+
+    async fn delete_many(&self, ids: &[ProductId]) -> Result<u64, Self::Error> {
+        sqlx::query(
+            "UPDATE products SET deleted_at = now() WHERE id = ANY($1) AND deleted_at IS NULL",
+        )
+        .bind(ids)
+        .execute(&self.pool)
+        .await
+        .map(|res| res.rows_affected())
+        .map_err(Into::into)
+    }
+
+    async fn restore(&self, id: ProductId) -> Result<Option<Product>, Self::Error> {
+        sqlx::query_as::<_, PgProductModel>(
+            "UPDATE products SET deleted_at = NULL, updated_at = CASE WHEN $2 THEN now() ELSE updated_at END, version = version + 1 WHERE id = $1 AND deleted_at IS NOT NULL RETURNING *",
+        )
+        .bind(id)
+        .bind(self.touch_on_restore)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|opt| opt.map(|model| model.into()))
+        .map_err(Into::into)
+    }
+
+    async fn set_category(
+        &self,
+        id: ProductId,
+        category_id: Option<CategoryId>,
+    ) -> Result<Option<Product>, Self::Error> {
+        sqlx::query_as::<_, PgProductModel>(
+            "UPDATE products SET category_id = $1, updated_at = now(), version = version + 1 WHERE id = $2 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(category_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|opt| opt.map(|model| model.into()))
+        .map_err(Into::into)
+    }
+
+    async fn set_stock(&self, id: ProductId, stock: u32) -> Result<Option<Product>, Self::Error> {
+        sqlx::query_as::<_, PgProductModel>(
+            "UPDATE products SET stock = $1, updated_at = now(), version = version + 1 WHERE id = $2 AND deleted_at IS NULL RETURNING *",
+        )
+        .bind(i64::from(stock))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|opt| opt.map(|model| model.into()))
+        .map_err(Into::into)
+    }
+
+    async fn reserve(&self, id: ProductId, quantity: u32) -> Result<Reservation, Self::Error> {
+        // The `stock >= $1` guard makes check and decrement a single atomic
+        // step; concurrent reservations queue on the row lock and re-check.
+        let model = sqlx::query_as::<_, PgProductModel>(
+            "UPDATE products SET stock = stock - $1, updated_at = now(), version = version + 1 WHERE id = $2 AND deleted_at IS NULL AND stock >= $1 RETURNING *",
+        )
+        .bind(i64::from(quantity))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(model) = model {
+            return Ok(Reservation::Reserved(Box::new(model.into())));
+        }
+
+        // Only tells the two failures apart; the stock may have changed since.
+        let available: Option<i32> =
+            sqlx::query_scalar("SELECT stock FROM products WHERE id = $1 AND deleted_at IS NULL")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(match available {
+            Some(available) => Reservation::Insufficient {
+                available: available.try_into().unwrap_or_default(),
+            },
+            None => Reservation::NotFound,
+        })
+    }
+
+    async fn read_price_history(&self, id: ProductId) -> Result<Vec<PriceChange>, Self::Error> {
+        sqlx::query_as::<_, PgPriceChangeModel>(
+            "SELECT old_price, new_price, changed_at FROM product_price_history WHERE product_id = $1 ORDER BY changed_at, id",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await
+        .map(|vec| vec.into_iter().map(|model| model.into()).collect())
+        .map_err(Into::into)
     }
 }
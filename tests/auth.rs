@@ -0,0 +1,357 @@
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, middleware::from_fn, web};
+use chrono::Utc;
+use jsonwebtoken::{EncodingKey, Header};
+
+use rust_backend::{
+    application::product_service::ProductService,
+    domain::price_unit::PriceUnit,
+    handlers::product_handlers::{
+        add_product, find_product, list_products, patch_product, remove_product,
+    },
+    middleware::auth::{AuthPolicy, AuthScope, AuthenticatedUser, Authenticator, authenticate},
+    repositories::memory_product_repository::InMemoryProductRepository,
+};
+
+const SECRET: &str = "test-secret";
+
+fn test_app(
+    scope: AuthScope,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let policy = AuthPolicy {
+        scope,
+        jwt_secret: Some(SECRET.into()),
+    };
+    let whoami = |req: HttpRequest| async move {
+        let user = req.extensions().get::<AuthenticatedUser>().cloned();
+        HttpResponse::Ok().body(user.map(|user| user.subject).unwrap_or_default())
+    };
+
+    App::new()
+        .app_data(web::Data::new(Authenticator::new(&policy)))
+        .service(
+            web::scope("/api/products")
+                .wrap(from_fn(authenticate))
+                .route("", web::get().to(whoami))
+                .route("", web::post().to(whoami)),
+        )
+        .route("/health", web::get().to(HttpResponse::Ok))
+}
+
+fn product_app(
+    scope: AuthScope,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    type Repo = InMemoryProductRepository;
+    let policy = AuthPolicy {
+        scope,
+        jwt_secret: Some(SECRET.into()),
+    };
+
+    App::new()
+        .app_data(web::Data::new(Authenticator::new(&policy)))
+        .app_data(web::Data::new(ProductService::new(Repo::default())))
+        .app_data(web::Data::new(PriceUnit::Cents))
+        .service(
+            web::scope("/api/products")
+                .wrap(from_fn(authenticate))
+                .route("", web::get().to(list_products::<Repo>))
+                .route("", web::post().to(add_product::<Repo>))
+                .route("/{id}", web::get().to(find_product::<Repo>))
+                .route("/{id}", web::patch().to(patch_product::<Repo>))
+                .route("/{id}", web::delete().to(remove_product::<Repo>)),
+        )
+}
+
+fn token(secret: &str, expires_in: i64) -> String {
+    let claims = serde_json::json!({
+        "sub": "alice",
+        "exp": Utc::now().timestamp() + expires_in,
+    });
+    encode(secret, &claims)
+}
+
+fn role_token(role: &str) -> String {
+    let claims = serde_json::json!({
+        "sub": "bob",
+        "role": role,
+        "exp": Utc::now().timestamp() + 3600,
+    });
+    encode(SECRET, &claims)
+}
+
+fn encode(secret: &str, claims: &serde_json::Value) -> String {
+    jsonwebtoken::encode(
+        &Header::default(),
+        claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn writes_need_a_valid_token() {
+    let app = actix_web::test::init_service(test_app(AuthScope::Writes)).await;
+
+    for authorization in [
+        None,
+        Some("Basic YWxpY2U6cHc=".to_owned()),
+        Some("Bearer not-a-jwt".to_owned()),
+        Some(format!("Bearer {}", token("other-secret", 3600))),
+        Some(format!("Bearer {}", token(SECRET, -3600))),
+    ] {
+        let mut req = actix_web::test::TestRequest::post().uri("/api/products");
+        if let Some(authorization) = &authorization {
+            req = req.insert_header(("Authorization", authorization.as_str()));
+        }
+        let resp = actix_web::test::call_service(&app, req.to_request()).await;
+
+        assert_eq!(resp.status(), 401, "{:?}", authorization);
+        assert_eq!(resp.headers().get("WWW-Authenticate").unwrap(), "Bearer");
+    }
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Authorization", format!("Bearer {}", token(SECRET, 3600))))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(actix_web::test::read_body(resp).await, "alice");
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn reads_pick_up_an_optional_token() {
+    let app = actix_web::test::init_service(test_app(AuthScope::Writes)).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header(("Authorization", format!("Bearer {}", token(SECRET, 3600))))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(actix_web::test::read_body(resp).await, "alice");
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header(("Authorization", format!("Bearer {}", token(SECRET, -3600))))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(actix_web::test::read_body(resp).await, "");
+}
+
+#[actix_web::test]
+async fn scope_all_also_covers_reads_but_not_health() {
+    let app = actix_web::test::init_service(test_app(AuthScope::All)).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/health")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn scope_off_lets_everything_through() {
+    let app = actix_web::test::init_service(test_app(AuthScope::Off)).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn only_admins_change_or_delete_products() {
+    let app = actix_web::test::init_service(product_app(AuthScope::All)).await;
+    let viewer = format!("Bearer {}", role_token("viewer"));
+    let admin = format!("Bearer {}", role_token("admin"));
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Authorization", viewer.as_str()))
+        .set_json(serde_json::json!({ "name": "Lamp", "description": "Desk lamp", "price": 1999 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let product: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    let uri = format!("/api/products/{}", product["id"].as_str().unwrap());
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&uri)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    for authorization in [viewer.clone(), format!("Bearer {}", token(SECRET, 3600))] {
+        let req = actix_web::test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(("Authorization", authorization.as_str()))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+    }
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", viewer.as_str()))
+        .set_json(serde_json::json!({ "price": 2499 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    for uri in ["/api/products", uri.as_str()] {
+        let req = actix_web::test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", viewer.as_str()))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+    }
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", admin.as_str()))
+        .set_json(serde_json::json!({ "price": 2499 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+}
+
+#[actix_web::test]
+async fn only_admins_list_deleted_products() {
+    let app = actix_web::test::init_service(product_app(AuthScope::Writes)).await;
+    let viewer = format!("Bearer {}", role_token("viewer"));
+    let admin = format!("Bearer {}", role_token("admin"));
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Authorization", admin.as_str()))
+        .set_json(serde_json::json!({ "name": "Lamp", "description": "Desk lamp", "price": 1999 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    let product: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&format!(
+            "/api/products/{}",
+            product["id"].as_str().unwrap()
+        ))
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    for uri in [
+        "/api/products?include_deleted=true",
+        "/api/products?include_deleted=true&stream=true",
+    ] {
+        let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401, "{uri}");
+
+        let req = actix_web::test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Authorization", viewer.as_str()))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403, "{uri}");
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header(("Authorization", viewer.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["items"], serde_json::json!([]));
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?include_deleted=true")
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["items"][0]["id"], product["id"]);
+}
+
+#[actix_web::test]
+async fn only_admins_find_a_deleted_product() {
+    let app = actix_web::test::init_service(product_app(AuthScope::Writes)).await;
+    let viewer = format!("Bearer {}", role_token("viewer"));
+    let admin = format!("Bearer {}", role_token("admin"));
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Authorization", admin.as_str()))
+        .set_json(serde_json::json!({ "name": "Lamp", "description": "Desk lamp", "price": 1999 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    let product: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    let uri = format!("/api/products/{}", product["id"].as_str().unwrap());
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    let flagged = format!("{uri}?include_deleted=true");
+    for (uri, authorization) in [
+        (uri.as_str(), Some(admin.as_str())),
+        (flagged.as_str(), None),
+        (flagged.as_str(), Some(viewer.as_str())),
+    ] {
+        let mut req = actix_web::test::TestRequest::get().uri(uri);
+        if let Some(authorization) = authorization {
+            req = req.insert_header(("Authorization", authorization));
+        }
+        let resp = actix_web::test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), 404, "{uri} {authorization:?}");
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&flagged)
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["id"], product["id"]);
+    assert!(body["deleted_at"].is_string());
+}
@@ -0,0 +1,188 @@
+use actix_web::{App, middleware::from_fn, web};
+use chrono::Utc;
+use jsonwebtoken::{EncodingKey, Header};
+use sqlx::PgPool;
+
+use rust_backend::{
+    application::{category_service::CategoryService, product_service::ProductService},
+    domain::price::Price,
+    handlers::category_handlers::{
+        add_category, find_category, list_categories, put_category, remove_category,
+    },
+    middleware::auth::{AuthPolicy, AuthScope, Authenticator, authenticate},
+    repositories::{
+        category_repository::PgCategoryRepository, product_repository::PgProductRepository,
+    },
+};
+
+const SECRET: &str = "test-secret";
+
+fn test_app(
+    pool: PgPool,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    type Repo = PgCategoryRepository;
+    let policy = AuthPolicy {
+        scope: AuthScope::Writes,
+        jwt_secret: Some(SECRET.into()),
+    };
+
+    App::new()
+        .app_data(web::Data::new(Authenticator::new(&policy)))
+        .app_data(web::Data::new(CategoryService::new(Repo::new(pool))))
+        .service(
+            web::scope("/api/categories")
+                .wrap(from_fn(authenticate))
+                .route("", web::get().to(list_categories::<Repo>))
+                .route("", web::post().to(add_category::<Repo>))
+                .route("/{id}", web::get().to(find_category::<Repo>))
+                .route("/{id}", web::put().to(put_category::<Repo>))
+                .route("/{id}", web::delete().to(remove_category::<Repo>)),
+        )
+}
+
+fn bearer(role: &str) -> String {
+    let claims = serde_json::json!({
+        "sub": "bob",
+        "role": role,
+        "exp": Utc::now().timestamp() + 3600,
+    });
+    let token = jsonwebtoken::encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(SECRET.as_bytes()),
+    )
+    .unwrap();
+    format!("Bearer {}", token)
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn only_admins_change_categories(pool: PgPool) {
+    let app = actix_web::test::init_service(test_app(pool)).await;
+    let viewer = bearer("viewer");
+    let admin = bearer("admin");
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/categories")
+        .insert_header(("Authorization", viewer.as_str()))
+        .set_json(serde_json::json!({ "name": "Tools" }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/categories")
+        .insert_header(("Authorization", admin.as_str()))
+        .set_json(serde_json::json!({ "name": "Tools" }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let tools: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    let uri = format!("/api/categories/{}", tools["id"].as_str().unwrap());
+
+    for req in [
+        actix_web::test::TestRequest::put()
+            .uri(&uri)
+            .set_json(serde_json::json!({ "name": "Hardware" })),
+        actix_web::test::TestRequest::delete().uri(&uri),
+    ] {
+        let req = req
+            .insert_header(("Authorization", viewer.as_str()))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Authorization", viewer.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn invalid_category_names_return_422(pool: PgPool) {
+    let app = actix_web::test::init_service(test_app(pool)).await;
+    let admin = bearer("admin");
+
+    for (name, error) in [
+        ("", "name must be 1 to 255 characters"),
+        ("   ", "name must not be empty"),
+    ] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/categories")
+            .insert_header(("Authorization", admin.as_str()))
+            .set_json(serde_json::json!({ "name": name }))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 422, "{:?}", name);
+        let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(body["error"], error);
+        assert_eq!(body["field"], "name");
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn category_conflicts_return_409(pool: PgPool) {
+    let app = actix_web::test::init_service(test_app(pool.clone())).await;
+    let products = ProductService::new(PgProductRepository::new(pool));
+    let admin = bearer("admin");
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/categories")
+        .insert_header(("Authorization", admin.as_str()))
+        .set_json(serde_json::json!({ "name": "Tools" }))
+        .to_request();
+    let tools: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    let uri = format!("/api/categories/{}", tools["id"].as_str().unwrap());
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/categories")
+        .insert_header(("Authorization", admin.as_str()))
+        .set_json(serde_json::json!({ "name": "tools" }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    let hammer = products
+        .add(
+            "Hammer".into(),
+            "Desc".into(),
+            Price::new(10).unwrap(),
+            Vec::new(),
+            None,
+        )
+        .await
+        .unwrap();
+    products
+        .assign_category(
+            hammer.id,
+            Some(serde_json::from_value(tools["id"].clone()).unwrap()),
+        )
+        .await
+        .unwrap();
+
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 409);
+
+    // A deleted product no longer holds the category.
+    products.remove(hammer.id).await.unwrap();
+    let req = actix_web::test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("Authorization", admin.as_str()))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 204);
+}
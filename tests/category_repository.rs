@@ -0,0 +1,129 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use rust_backend::{
+    application::{
+        category_service::{CategoryRepository, CategoryService, CategoryServiceError},
+        filtering::ProductFilter,
+        pagination::Page,
+        product_service::{ClassifyError, ProductRepository, ProductService, ProductServiceError},
+        sorting::Sort,
+    },
+    domain::price::Price,
+    repositories::{
+        category_repository::PgCategoryRepository, product_repository::PgProductRepository,
+    },
+};
+
+fn price(cents: u32) -> Price {
+    Price::new(cents).unwrap()
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn category_names_are_unique_ignoring_case(pool: PgPool) {
+    let repo = PgCategoryRepository::new(pool);
+
+    let tools = repo.create("Tools".into()).await.unwrap();
+    repo.create("Books".into()).await.unwrap();
+
+    let error = repo.create("tools".into()).await.err().unwrap();
+    assert!(error.is_conflict());
+
+    let renamed = repo
+        .rename(tools.id, "Hardware".into())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(renamed.name, "Hardware");
+    assert!(
+        repo.rename(Uuid::new_v4().into(), "Garden".into())
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let names: Vec<_> = repo
+        .read_all()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|category| category.name)
+        .collect();
+    assert_eq!(names, ["Books", "Hardware"]);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn products_can_be_filtered_by_category(pool: PgPool) {
+    let categories = PgCategoryRepository::new(pool.clone());
+    let repo = PgProductRepository::new(pool);
+
+    let tools = categories.create("Tools".into()).await.unwrap();
+    let hammer = repo
+        .create("Hammer".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    repo.create("Novel".into(), "Desc".into(), price(20), Vec::new(), None)
+        .await
+        .unwrap();
+
+    let hammer = repo
+        .set_category(hammer.id, Some(tools.id))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(hammer.category_id, Some(tools.id));
+    assert_eq!(hammer.version, 2);
+
+    let filter = ProductFilter::default().with_category(Some(tools.id));
+    let products = repo
+        .read_sorted(&filter, Sort::default(), Page::new(None, None), false)
+        .await
+        .unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name, "Hammer");
+    assert_eq!(repo.count(&filter, false).await.unwrap(), 1);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn assigning_a_missing_category_is_rejected(pool: PgPool) {
+    let service = ProductService::new(PgProductRepository::new(pool));
+
+    let product = service
+        .add("Hammer".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    let result = service
+        .assign_category(product.id, Some(Uuid::new_v4().into()))
+        .await;
+
+    assert!(matches!(result, Err(ProductServiceError::Validation(_))));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn categories_with_live_products_cannot_be_deleted(pool: PgPool) {
+    let categories = CategoryService::new(PgCategoryRepository::new(pool.clone()));
+    let products = ProductService::new(PgProductRepository::new(pool));
+
+    let tools = categories.add("Tools".into()).await.unwrap();
+    let hammer = products
+        .add("Hammer".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    products
+        .assign_category(hammer.id, Some(tools.id))
+        .await
+        .unwrap();
+
+    let result = categories.remove(tools.id).await;
+    assert!(matches!(result, Err(CategoryServiceError::InUse)));
+
+    // Deleted products don't hold the category up, and come back without it.
+    products.remove(hammer.id).await.unwrap();
+    categories.remove(tools.id).await.unwrap();
+    assert!(matches!(
+        categories.find(tools.id).await,
+        Err(CategoryServiceError::NotFound)
+    ));
+    let hammer = products.restore(hammer.id).await.unwrap();
+    assert_eq!(hammer.category_id, None);
+}
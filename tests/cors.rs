@@ -0,0 +1,109 @@
+use actix_web::{App, HttpResponse, web};
+
+use rust_backend::middleware::cors::{cors, default_allowed_headers};
+
+fn test_app() -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new().wrap(cors(default_allowed_headers())).route(
+        "/api/products/{id}",
+        web::patch().to(|| async { HttpResponse::Ok().finish() }),
+    )
+}
+
+#[actix_web::test]
+async fn preflight_allows_patch_with_if_match() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/api/products/1")
+        .insert_header(("Origin", "https://shop.example.com"))
+        .insert_header(("Access-Control-Request-Method", "PATCH"))
+        .insert_header(("Access-Control-Request-Headers", "content-type, if-match"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    let allow_headers = resp
+        .headers()
+        .get("Access-Control-Allow-Headers")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(allow_headers.contains("if-match"));
+    assert!(!allow_headers.contains("idempotency-key"));
+
+    let allow_methods = resp
+        .headers()
+        .get("Access-Control-Allow-Methods")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(allow_methods.contains("PATCH"));
+}
+
+#[actix_web::test]
+async fn preflight_rejects_unlisted_header() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/api/products/1")
+        .insert_header(("Origin", "https://shop.example.com"))
+        .insert_header(("Access-Control-Request-Method", "PATCH"))
+        .insert_header(("Access-Control-Request-Headers", "x-unlisted"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn preflight_allows_bearer_tokens() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/api/products/1")
+        .insert_header(("Origin", "https://shop.example.com"))
+        .insert_header(("Access-Control-Request-Method", "PATCH"))
+        .insert_header((
+            "Access-Control-Request-Headers",
+            "authorization, content-type",
+        ))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    let allow_headers = resp
+        .headers()
+        .get("Access-Control-Allow-Headers")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(allow_headers.contains("authorization"));
+}
+
+#[actix_web::test]
+async fn preflight_rejects_unlisted_method() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri("/api/products/1")
+        .insert_header(("Origin", "https://shop.example.com"))
+        .insert_header(("Access-Control-Request-Method", "TRACE"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
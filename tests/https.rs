@@ -0,0 +1,133 @@
+use actix_web::{App, HttpResponse, middleware::from_fn, web};
+
+use rust_backend::middleware::https::{
+    FORWARDED_PROTO_HEADER, HttpsEnforcement, HttpsPolicy, enforce_https,
+};
+
+fn test_app(
+    policy: HttpsPolicy,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .service(
+            web::scope("/api/products")
+                .wrap(from_fn(move |req, next| enforce_https(policy, req, next)))
+                .route("", web::get().to(|| async { HttpResponse::Ok().finish() })),
+        )
+        .route("/livez", web::get().to(HttpResponse::Ok))
+}
+
+#[actix_web::test]
+async fn plaintext_request_is_rejected() {
+    let app = actix_web::test::init_service(test_app(HttpsPolicy {
+        enforcement: HttpsEnforcement::Reject,
+        trust_forwarded_proto: true,
+    }))
+    .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header((FORWARDED_PROTO_HEADER, "http"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body, serde_json::json!({ "error": "HTTPS is required." }));
+}
+
+#[actix_web::test]
+async fn probes_outside_the_api_are_not_enforced() {
+    let app = actix_web::test::init_service(test_app(HttpsPolicy {
+        enforcement: HttpsEnforcement::Reject,
+        trust_forwarded_proto: true,
+    }))
+    .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/livez")
+        .insert_header((FORWARDED_PROTO_HEADER, "http"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn plaintext_request_is_redirected() {
+    let app = actix_web::test::init_service(test_app(HttpsPolicy {
+        enforcement: HttpsEnforcement::Redirect,
+        trust_forwarded_proto: true,
+    }))
+    .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?limit=5")
+        .insert_header(("Host", "shop.example.com"))
+        .insert_header((FORWARDED_PROTO_HEADER, "http"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 308);
+    assert_eq!(
+        resp.headers().get("Location").unwrap(),
+        "https://shop.example.com/api/products?limit=5"
+    );
+}
+
+#[actix_web::test]
+async fn forwarded_https_request_is_allowed() {
+    let app = actix_web::test::init_service(test_app(HttpsPolicy {
+        enforcement: HttpsEnforcement::Reject,
+        trust_forwarded_proto: true,
+    }))
+    .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header((FORWARDED_PROTO_HEADER, "https"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn forwarded_proto_is_ignored_when_untrusted() {
+    let app = actix_web::test::init_service(test_app(HttpsPolicy {
+        enforcement: HttpsEnforcement::Reject,
+        trust_forwarded_proto: false,
+    }))
+    .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header((FORWARDED_PROTO_HEADER, "https"))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[actix_web::test]
+async fn enforcement_off_allows_plaintext() {
+    let app = actix_web::test::init_service(test_app(HttpsPolicy {
+        enforcement: HttpsEnforcement::Off,
+        trust_forwarded_proto: false,
+    }))
+    .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
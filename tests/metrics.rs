@@ -0,0 +1,49 @@
+use actix_web::{App, HttpResponse, middleware::from_fn, web};
+
+use rust_backend::{
+    handlers::metrics::metrics,
+    middleware::metrics::{Metrics, track_metrics},
+};
+
+fn test_app() -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .wrap(from_fn(track_metrics))
+        .app_data(web::Data::new(Metrics::default()))
+        .route("/metrics", web::get().to(metrics))
+        .route(
+            "/api/products/{id}",
+            web::get().to(|| async { HttpResponse::NotFound().finish() }),
+        )
+}
+
+#[actix_web::test]
+async fn requests_are_labelled_by_route_template() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for uri in ["/api/products/1", "/api/products/2", "/no/such/path"] {
+        let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/metrics")
+        .to_request();
+    let body = actix_web::test::call_and_read_body(&app, req).await;
+    let text = std::str::from_utf8(&body).unwrap();
+
+    assert!(text.contains(
+        "http_requests_total{method=\"GET\",route=\"/api/products/{id}\",status=\"404\"} 2"
+    ));
+    assert!(
+        text.contains("http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1")
+    );
+    assert!(!text.contains("/api/products/1"));
+}
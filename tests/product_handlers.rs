@@ -1,84 +1,24 @@
+use std::env::VarError;
+
 use actix_web::{App, web};
 use uuid::Uuid;
 
 use rust_backend::{
-    application::product_service::{ProductRepository, ProductService},
-    domain::product::Product,
+    application::product_service::ProductService, config::Config, domain::price_unit::PriceUnit,
+    handlers::query::QueryLimits,
+    repositories::memory_product_repository::InMemoryProductRepository,
 };
 
-#[derive(Default)]
-struct MockProductRepository {
-    products: std::sync::Mutex<Vec<Product>>,
-}
-
-#[derive(Debug)]
-struct MockError;
-impl std::fmt::Display for MockError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Mock repository error")
-    }
-}
-impl std::error::Error for MockError {}
-
-impl ProductRepository for MockProductRepository {
-    type Error = MockError;
-
-    async fn create(
-        &self,
-        name: String,
-        description: String,
-        price: u32,
-    ) -> Result<Product, Self::Error> {
-        let product = Product {
-            id: Uuid::new_v4(),
-            name,
-            description,
-            price,
-        };
-
-        self.products.lock().unwrap().push(product.clone());
-        Ok(product)
-    }
-
-    async fn read_all(&self) -> Result<Vec<Product>, Self::Error> {
-        Ok(self.products.lock().unwrap().clone())
-    }
-
-    async fn read_one(&self, id: Uuid) -> Result<Option<Product>, Self::Error> {
-        Ok(self
-            .products
-            .lock()
-            .unwrap()
+fn config(vars: &[(&str, &str)]) -> Config {
+    Config::from_lookup(|name| match name {
+        "DATABASE_URL" => Ok("postgres://localhost/db".into()),
+        _ => vars
             .iter()
-            .find(|p| p.id == id)
-            .cloned())
-    }
-
-    async fn update(
-        &self,
-        id: Uuid,
-        name: String,
-        description: String,
-        price: u32,
-    ) -> Result<Option<Product>, Self::Error> {
-        let mut products = self.products.lock().unwrap();
-        if let Some(p) = products.iter_mut().find(|p| p.id == id) {
-            p.name = name;
-            p.description = description;
-            p.price = price;
-            return Ok(Some(p.clone()));
-        }
-
-        Ok(None)
-    }
-
-    async fn delete(&self, id: Uuid) -> Result<bool, Self::Error> {
-        let mut products = self.products.lock().unwrap();
-        let len_before = products.len();
-        products.retain(|p| p.id != id);
-
-        Ok(products.len() != len_before)
-    }
+            .find(|(var, _)| *var == name)
+            .map(|(_, value)| value.to_string())
+            .ok_or(VarError::NotPresent),
+    })
+    .unwrap()
 }
 
 fn test_app() -> App<
@@ -90,29 +30,108 @@ fn test_app() -> App<
         InitError = (),
     >,
 > {
-    type Repo = MockProductRepository;
+    type Repo = InMemoryProductRepository;
     let repo = Repo::default();
     let service = ProductService::new(repo);
 
-    App::new().app_data(web::Data::new(service)).service(
-        web::scope("/api/products")
-            .route(
-                "",
-                web::get().to(rust_backend::handlers::product_handlers::list_products::<Repo>),
-            )
-            .route(
-                "",
-                web::post().to(rust_backend::handlers::product_handlers::add_product::<Repo>),
-            )
-            .route(
-                "/{id}",
-                web::get().to(rust_backend::handlers::product_handlers::find_product::<Repo>),
-            )
-            .route(
-                "/{id}",
-                web::delete().to(rust_backend::handlers::product_handlers::remove_product::<Repo>),
-            ),
-    )
+    App::new()
+        .app_data(web::Data::new(service))
+        .app_data(web::Data::new(PriceUnit::Cents))
+        .app_data(rust_backend::handlers::json::json_config(1024))
+        .app_data(web::Data::new(config(&[]).limits()))
+        .service(
+            web::scope("/api/products")
+                .route(
+                    "",
+                    web::get().to(rust_backend::handlers::product_handlers::list_products::<Repo>),
+                )
+                .route(
+                    "",
+                    web::post().to(rust_backend::handlers::product_handlers::add_product::<Repo>),
+                )
+                .route(
+                    "/capabilities",
+                    web::get().to(rust_backend::handlers::product_handlers::capabilities),
+                )
+                .route(
+                    "/recent",
+                    web::get()
+                        .to(rust_backend::handlers::product_handlers::list_recent_products::<Repo>),
+                )
+                .route(
+                    "/diff",
+                    web::get().to(rust_backend::handlers::product_handlers::diff_products::<Repo>),
+                )
+                .route(
+                    "/bulk-delete",
+                    web::post()
+                        .to(rust_backend::handlers::product_handlers::bulk_delete_products::<Repo>),
+                )
+                .route(
+                    "/export.csv",
+                    web::get()
+                        .to(rust_backend::handlers::product_handlers::export_products::<Repo>),
+                )
+                .route(
+                    "/stats",
+                    web::get().to(rust_backend::handlers::product_handlers::product_stats::<Repo>),
+                )
+                .route(
+                    "/import",
+                    web::post()
+                        .to(rust_backend::handlers::product_handlers::import_products::<Repo>),
+                )
+                .route(
+                    "/by-sku/{sku}",
+                    web::get()
+                        .to(rust_backend::handlers::product_handlers::find_product_by_sku::<Repo>),
+                )
+                .route(
+                    "/slug/{slug}",
+                    web::get()
+                        .to(rust_backend::handlers::product_handlers::find_product_by_slug::<Repo>),
+                )
+                .route(
+                    "/{id}",
+                    web::get().to(rust_backend::handlers::product_handlers::find_product::<Repo>),
+                )
+                .route(
+                    "/{id}",
+                    web::put().to(rust_backend::handlers::product_handlers::put_product::<Repo>),
+                )
+                .route(
+                    "/{id}",
+                    web::patch()
+                        .to(rust_backend::handlers::product_handlers::patch_product::<Repo>),
+                )
+                .route(
+                    "/{id}/restore",
+                    web::post()
+                        .to(rust_backend::handlers::product_handlers::restore_product::<Repo>),
+                )
+                .route(
+                    "/{id}/stock",
+                    web::put().to(rust_backend::handlers::product_handlers::set_stock::<Repo>),
+                )
+                .route(
+                    "/{id}/reserve",
+                    web::post().to(rust_backend::handlers::product_handlers::reserve_stock::<Repo>),
+                )
+                .route(
+                    "/{id}/category",
+                    web::put()
+                        .to(rust_backend::handlers::product_handlers::assign_category::<Repo>),
+                )
+                .route(
+                    "/{id}/price-history",
+                    web::get().to(rust_backend::handlers::product_handlers::price_history::<Repo>),
+                )
+                .route(
+                    "/{id}",
+                    web::delete()
+                        .to(rust_backend::handlers::product_handlers::remove_product::<Repo>),
+                ),
+        )
 }
 
 #[actix_web::test]
@@ -156,6 +175,9 @@ async fn find_product_returns_404() {
 
     let resp = actix_web::test::call_service(&app, req).await;
     assert_eq!(resp.status(), 404);
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body, serde_json::json!({ "error": "not found" }));
 }
 
 #[actix_web::test]
@@ -184,3 +206,1543 @@ async fn delete_product_return_204() {
     let delete_resp = actix_web::test::call_service(&app, delete_req).await;
     assert_eq!(delete_resp.status(), 204);
 }
+
+#[actix_web::test]
+async fn bulk_delete_reports_how_many_were_deleted() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let mut ids = Vec::new();
+    for name in ["A", "B"] {
+        let payload = serde_json::json!({
+            "name": name,
+            "description": "Desc",
+            "price": 1
+        });
+        let create_req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(&payload)
+            .to_request();
+        let create_resp: serde_json::Value =
+            actix_web::test::call_and_read_body_json(&app, create_req).await;
+        ids.push(create_resp["id"].clone());
+    }
+    ids.push(serde_json::json!(Uuid::new_v4()));
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products/bulk-delete")
+        .set_json(serde_json::json!({ "ids": ids }))
+        .to_request();
+    let resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp, serde_json::json!({ "deleted": 2 }));
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 0);
+}
+
+#[actix_web::test]
+async fn restore_product_brings_back_deleted_product() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Temp",
+        "description": "Temp",
+        "price": 1
+    });
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+    let create_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    let id = create_resp["id"].as_str().unwrap();
+
+    let restore_req = actix_web::test::TestRequest::post()
+        .uri(&format!("/api/products/{}/restore", id))
+        .to_request();
+    let restore_resp = actix_web::test::call_service(&app, restore_req).await;
+    assert_eq!(restore_resp.status(), 404);
+
+    for expected in [204, 404] {
+        let delete_req = actix_web::test::TestRequest::delete()
+            .uri(&format!("/api/products/{}", id))
+            .to_request();
+        let delete_resp = actix_web::test::call_service(&app, delete_req).await;
+        assert_eq!(delete_resp.status(), expected);
+    }
+
+    let restore_req = actix_web::test::TestRequest::post()
+        .uri(&format!("/api/products/{}/restore", id))
+        .to_request();
+    let restore_resp = actix_web::test::call_service(&app, restore_req).await;
+    assert_eq!(restore_resp.status(), 200);
+
+    let get_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}", id))
+        .to_request();
+    let get_resp = actix_web::test::call_service(&app, get_req).await;
+    assert_eq!(get_resp.status(), 200);
+}
+
+#[actix_web::test]
+async fn list_products_filters_by_category() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let category_id = Uuid::new_v4().to_string();
+
+    let mut ids = Vec::new();
+    for name in ["Hammer", "Novel"] {
+        let create_req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": 1 }))
+            .to_request();
+        let create_resp: serde_json::Value =
+            actix_web::test::call_and_read_body_json(&app, create_req).await;
+        assert_eq!(create_resp["category_id"], serde_json::Value::Null);
+        ids.push(create_resp["id"].as_str().unwrap().to_owned());
+    }
+
+    let assign_req = actix_web::test::TestRequest::put()
+        .uri(&format!("/api/products/{}/category", ids[0]))
+        .set_json(serde_json::json!({ "category_id": category_id }))
+        .to_request();
+    let assign_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, assign_req).await;
+    assert_eq!(assign_resp["category_id"], category_id.as_str());
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products?category_id={}", category_id))
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 1);
+    assert_eq!(list_resp["items"][0]["name"], "Hammer");
+
+    let unassign_req = actix_web::test::TestRequest::put()
+        .uri(&format!("/api/products/{}/category", ids[0]))
+        .set_json(serde_json::json!({ "category_id": null }))
+        .to_request();
+    let unassign_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, unassign_req).await;
+    assert_eq!(unassign_resp["category_id"], serde_json::Value::Null);
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products?category_id={}", category_id))
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 0);
+}
+
+#[actix_web::test]
+async fn list_products_filters_by_tag() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for (name, tags) in [
+        ("Lamp", serde_json::json!([" sale ", "new", "sale"])),
+        ("Chair", serde_json::json!(["new"])),
+    ] {
+        let create_req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(
+                serde_json::json!({ "name": name, "description": "Desc", "price": 1, "tags": tags }),
+            )
+            .to_request();
+        actix_web::test::call_service(&app, create_req).await;
+    }
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?tag=sale")
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 1);
+    assert_eq!(list_resp["items"][0]["name"], "Lamp");
+    assert_eq!(
+        list_resp["items"][0]["tags"],
+        serde_json::json!(["sale", "new"])
+    );
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?tag=new")
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 2);
+}
+
+#[actix_web::test]
+async fn reserve_takes_stock_until_it_runs_out() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "name": "Lamp", "description": "Desc", "price": 1 }))
+        .to_request();
+    let create_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    assert_eq!(create_resp["stock"], 0);
+    let id = create_resp["id"].as_str().unwrap();
+
+    let reserve = |quantity: u32| {
+        actix_web::test::TestRequest::post()
+            .uri(&format!("/api/products/{}/reserve", id))
+            .set_json(serde_json::json!({ "quantity": quantity }))
+            .to_request()
+    };
+
+    let resp = actix_web::test::call_service(&app, reserve(1)).await;
+    assert_eq!(resp.status(), 409);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body, serde_json::json!({ "error": "only 0 in stock" }));
+
+    let stock_req = actix_web::test::TestRequest::put()
+        .uri(&format!("/api/products/{}/stock", id))
+        .set_json(serde_json::json!({ "stock": 3 }))
+        .to_request();
+    let stock_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, stock_req).await;
+    assert_eq!(stock_resp["stock"], 3);
+
+    let resp = actix_web::test::call_service(&app, reserve(2)).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["stock"], 1);
+
+    let resp = actix_web::test::call_service(&app, reserve(2)).await;
+    assert_eq!(resp.status(), 409);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body, serde_json::json!({ "error": "only 1 in stock" }));
+
+    let resp = actix_web::test::call_service(&app, reserve(0)).await;
+    assert_eq!(resp.status(), 422);
+
+    let get_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}", id))
+        .to_request();
+    let get_resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, get_req).await;
+    assert_eq!(get_resp["stock"], 1);
+}
+
+#[actix_web::test]
+async fn products_are_found_by_normalized_sku() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let create = |sku: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(
+                serde_json::json!({ "name": "Lamp", "description": "Desc", "price": 1, "sku": sku }),
+            )
+            .to_request()
+    };
+
+    let resp = actix_web::test::call_service(&app, create(" lmp-01 ")).await;
+    assert_eq!(resp.status(), 201);
+    let product: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(product["sku"], "LMP-01");
+    let id = product["id"].as_str().unwrap();
+
+    let resp = actix_web::test::call_service(&app, create("LMP-01")).await;
+    assert_eq!(resp.status(), 409);
+
+    let get_req = actix_web::test::TestRequest::get()
+        .uri("/api/products/by-sku/lmp-01")
+        .to_request();
+    let found: serde_json::Value = actix_web::test::call_and_read_body_json(&app, get_req).await;
+    assert_eq!(found["id"], id);
+
+    for (sku, status) in [("lmp-01", 200), ("LMP-02", 422)] {
+        let put_req = actix_web::test::TestRequest::put()
+            .uri(&format!("/api/products/{}", id))
+            .set_json(
+                serde_json::json!({ "name": "Lamp", "description": "Desc", "price": 2, "sku": sku }),
+            )
+            .to_request();
+        let resp = actix_web::test::call_service(&app, put_req).await;
+        assert_eq!(resp.status(), status, "{}", sku);
+    }
+}
+
+#[actix_web::test]
+async fn a_missing_sku_can_be_set_once_on_update() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "name": "Lamp", "description": "Desc", "price": 1 }))
+        .to_request();
+    let product: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    assert!(product["sku"].is_null());
+    let id = product["id"].as_str().unwrap();
+
+    for (sku, status) in [("lmp-9", 200), ("lmp-10", 422), ("LMP-9", 200)] {
+        let put_req = actix_web::test::TestRequest::put()
+            .uri(&format!("/api/products/{}", id))
+            .set_json(
+                serde_json::json!({ "name": "Lamp", "description": "Desc", "price": 2, "sku": sku }),
+            )
+            .to_request();
+        let resp = actix_web::test::call_service(&app, put_req).await;
+        assert_eq!(resp.status(), status, "{}", sku);
+        let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        if status == 200 {
+            assert_eq!(body["sku"], "LMP-9");
+        } else {
+            assert_eq!(body["field"], "sku");
+        }
+    }
+}
+
+#[actix_web::test]
+async fn list_products_includes_deleted_on_request() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Temp",
+        "description": "Temp",
+        "price": 1
+    });
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+    let create_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    assert!(create_resp.get("deleted_at").is_none());
+    let id = create_resp["id"].as_str().unwrap();
+
+    let delete_req = actix_web::test::TestRequest::delete()
+        .uri(&format!("/api/products/{}", id))
+        .to_request();
+    actix_web::test::call_service(&app, delete_req).await;
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 0);
+
+    let list_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?include_deleted=true")
+        .to_request();
+    let list_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, list_req).await;
+    assert_eq!(list_resp["total"], 1);
+    assert_eq!(list_resp["items"][0]["id"], id);
+    assert!(list_resp["items"][0]["deleted_at"].is_string());
+}
+
+#[actix_web::test]
+async fn find_product_answers_if_none_match() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Book",
+        "description": "A nice book",
+        "price": 100
+    });
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+    let created: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    let uri = format!("/api/products/{}", created["id"].as_str().unwrap());
+
+    let get_req = actix_web::test::TestRequest::get().uri(&uri).to_request();
+    let get_resp = actix_web::test::call_service(&app, get_req).await;
+    assert_eq!(get_resp.status(), 200);
+    let etag = get_resp.headers().get("ETag").unwrap().clone();
+    assert!(get_resp.headers().contains_key("Last-Modified"));
+
+    for if_none_match in [etag.to_str().unwrap(), "W/\"1\"", "\"0\", \"1\"", "*"] {
+        let get_req = actix_web::test::TestRequest::get()
+            .uri(&uri)
+            .insert_header(("If-None-Match", if_none_match))
+            .to_request();
+        let get_resp = actix_web::test::call_service(&app, get_req).await;
+        assert_eq!(get_resp.status(), 304, "{}", if_none_match);
+        assert_eq!(get_resp.headers().get("ETag"), Some(&etag));
+        assert_eq!(get_resp.headers().get("Vary").unwrap(), "accept");
+        let body = actix_web::test::read_body(get_resp).await;
+        assert!(body.is_empty());
+    }
+
+    // Other representations of the same version get their own tags.
+    let v2_req = actix_web::test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Accept", rust_backend::handlers::api_version::V2_MEDIA_TYPE))
+        .insert_header(("If-None-Match", etag.clone()))
+        .to_request();
+    let v2_resp = actix_web::test::call_service(&app, v2_req).await;
+    assert_eq!(v2_resp.status(), 200);
+    assert_eq!(v2_resp.headers().get("ETag").unwrap(), "\"1-v2\"");
+
+    let taxed_req = actix_web::test::TestRequest::get()
+        .uri(&format!("{}?tax_rate=0.0825", uri))
+        .insert_header(("If-None-Match", etag.clone()))
+        .to_request();
+    let taxed_resp = actix_web::test::call_service(&app, taxed_req).await;
+    assert_eq!(taxed_resp.status(), 200);
+    assert_eq!(taxed_resp.headers().get("ETag").unwrap(), "\"1-tax825\"");
+
+    let patch_req = actix_web::test::TestRequest::patch()
+        .uri(&uri)
+        .set_json(serde_json::json!({ "price": 150 }))
+        .to_request();
+    actix_web::test::call_service(&app, patch_req).await;
+
+    let get_req = actix_web::test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("If-None-Match", etag))
+        .to_request();
+    let get_resp = actix_web::test::call_service(&app, get_req).await;
+    assert_eq!(get_resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(get_resp).await;
+    assert_eq!(body["price"], 150);
+}
+
+#[actix_web::test]
+async fn conditional_update_checks_if_match() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Book",
+        "description": "A nice book",
+        "price": 100
+    });
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+    let create_resp = actix_web::test::call_service(&app, create_req).await;
+    assert_eq!(create_resp.headers().get("ETag").unwrap(), "\"1\"");
+    let created: serde_json::Value = actix_web::test::read_body_json(create_resp).await;
+    assert_eq!(created["version"], 1);
+    let id = created["id"].as_str().unwrap();
+
+    let patch_req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/api/products/{}", id))
+        .insert_header(("If-Match", "\"1\""))
+        .set_json(serde_json::json!({ "price": 150 }))
+        .to_request();
+    let patch_resp = actix_web::test::call_service(&app, patch_req).await;
+    assert_eq!(patch_resp.status(), 200);
+    assert_eq!(patch_resp.headers().get("ETag").unwrap(), "\"2\"");
+
+    let patch_req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/api/products/{}", id))
+        .insert_header(("Accept", rust_backend::handlers::api_version::V2_MEDIA_TYPE))
+        .insert_header(("If-Match", "\"2-v2\""))
+        .set_json(serde_json::json!({ "price": 150 }))
+        .to_request();
+    let patch_resp = actix_web::test::call_service(&app, patch_req).await;
+    assert_eq!(patch_resp.status(), 200);
+    assert_eq!(patch_resp.headers().get("ETag").unwrap(), "\"3-v2\"");
+
+    for if_match in ["\"1\"", "W/\"3\"", "\"2-v2\"", "\"two\""] {
+        let put_req = actix_web::test::TestRequest::put()
+            .uri(&format!("/api/products/{}", id))
+            .insert_header(("If-Match", if_match))
+            .set_json(&payload)
+            .to_request();
+        let put_resp = actix_web::test::call_service(&app, put_req).await;
+        assert_eq!(put_resp.status(), 412, "{}", if_match);
+    }
+
+    let put_req = actix_web::test::TestRequest::put()
+        .uri(&format!("/api/products/{}", id))
+        .insert_header(("If-Match", "*"))
+        .set_json(&payload)
+        .to_request();
+    let put_resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, put_req).await;
+    assert_eq!(put_resp["price"], 100);
+    assert_eq!(put_resp["version"], 4);
+}
+
+#[actix_web::test]
+async fn patch_product_updates_given_fields() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Book",
+        "description": "A nice book",
+        "price": 100
+    });
+
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+
+    let create_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    let id = create_resp["id"].as_str().unwrap();
+
+    let patch_req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/api/products/{}", id))
+        .set_json(serde_json::json!({ "price": 150 }))
+        .to_request();
+
+    let patch_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, patch_req).await;
+    assert_eq!(patch_resp["name"], "Book");
+    assert_eq!(patch_resp["description"], "A nice book");
+    assert_eq!(patch_resp["price"], 150);
+
+    let missing_req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/api/products/{}", Uuid::new_v4()))
+        .set_json(serde_json::json!({ "name": "Other" }))
+        .to_request();
+
+    let missing_resp = actix_web::test::call_service(&app, missing_req).await;
+    assert_eq!(missing_resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn find_product_negotiates_v2_shape() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Book",
+        "description": "A nice book",
+        "price": 100
+    });
+
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+
+    let create_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    let id = create_resp["id"].as_str().unwrap();
+    assert!(create_resp.get("updated_at").is_none());
+
+    let find_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}", id))
+        .insert_header(("Accept", rust_backend::handlers::api_version::V2_MEDIA_TYPE))
+        .to_request();
+
+    let find_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, find_req).await;
+    assert_eq!(find_resp["id"], id);
+    assert!(find_resp["updated_at"].is_string());
+}
+
+#[actix_web::test]
+async fn list_products_window_headers() {
+    use rust_backend::handlers::product_handlers::{NEWEST_UPDATED_HEADER, OLDEST_CREATED_HEADER};
+
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let empty_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?window_headers=true")
+        .to_request();
+
+    let empty_resp = actix_web::test::call_service(&app, empty_req).await;
+    assert_eq!(empty_resp.status(), 200);
+    assert!(empty_resp.headers().get(OLDEST_CREATED_HEADER).is_none());
+    assert!(empty_resp.headers().get(NEWEST_UPDATED_HEADER).is_none());
+
+    for name in ["First", "Second"] {
+        let payload = serde_json::json!({
+            "name": name,
+            "description": "Desc",
+            "price": 10
+        });
+        let create_req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(&payload)
+            .to_request();
+        actix_web::test::call_service(&app, create_req).await;
+    }
+
+    let plain_req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+
+    let plain_resp = actix_web::test::call_service(&app, plain_req).await;
+    assert!(plain_resp.headers().get(OLDEST_CREATED_HEADER).is_none());
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?window_headers=true")
+        .insert_header(("Accept", rust_backend::handlers::api_version::V2_MEDIA_TYPE))
+        .to_request();
+
+    let resp = actix_web::test::call_service(&app, req).await;
+    let oldest = resp.headers().get(OLDEST_CREATED_HEADER).cloned().unwrap();
+    let newest = resp.headers().get(NEWEST_UPDATED_HEADER).cloned().unwrap();
+
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    let products = body["items"].as_array().unwrap();
+    assert_eq!(products[1]["created_at"], oldest.to_str().unwrap());
+    assert_eq!(products[0]["updated_at"], newest.to_str().unwrap());
+}
+
+#[actix_web::test]
+async fn find_product_with_tax_rate_returns_gross_price() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Book",
+        "description": "A nice book",
+        "price": 999
+    });
+
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+
+    let create_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    let id = create_resp["id"].as_str().unwrap();
+
+    let find_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}?tax_rate=0.0825", id))
+        .to_request();
+
+    let find_resp: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, find_req).await;
+    assert_eq!(find_resp["price"], 999);
+    assert_eq!(find_resp["gross_price"], 1081);
+
+    let invalid_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}?tax_rate=0.2%25", id))
+        .to_request();
+
+    let invalid_resp = actix_web::test::call_service(&app, invalid_req).await;
+    assert_eq!(invalid_resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn list_recent_products_parses_window() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let payload = serde_json::json!({
+        "name": "Book",
+        "description": "A nice book",
+        "price": 100
+    });
+
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(&payload)
+        .to_request();
+    actix_web::test::call_service(&app, create_req).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/recent?within=1h")
+        .to_request();
+
+    let resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(resp.as_array().unwrap().len(), 1);
+
+    for within in ["", "1", "h", "10y", "-1h", "0m"] {
+        let req = actix_web::test::TestRequest::get()
+            .uri(&format!("/api/products/recent?within={}", within))
+            .to_request();
+
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", within);
+    }
+}
+
+#[actix_web::test]
+async fn add_product_type_mismatch_returns_422() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let cases = [
+        (
+            serde_json::json!({ "name": "Book", "description": "Desc", "price": "abc" }),
+            serde_json::json!({
+                "error": "price must be a number",
+                "field": "price",
+                "code": "INVALID_TYPE",
+                "expected": "number",
+            }),
+        ),
+        (
+            serde_json::json!({ "name": 42, "description": "Desc", "price": 100 }),
+            serde_json::json!({
+                "error": "name must be a string",
+                "field": "name",
+                "code": "INVALID_TYPE",
+                "expected": "string",
+            }),
+        ),
+        (
+            serde_json::json!({ "name": "Book", "description": "Desc", "price": -5 }),
+            serde_json::json!({
+                "error": "price must be a non-negative decimal number",
+                "field": "price",
+                "fields": [{
+                    "field": "price",
+                    "code": "range",
+                    "message": "price must be a non-negative decimal number",
+                }],
+            }),
+        ),
+        (
+            serde_json::json!({ "name": "Book", "description": "Desc" }),
+            serde_json::json!({
+                "error": "price is required",
+                "field": "price",
+                "code": "MISSING_FIELD",
+            }),
+        ),
+    ];
+
+    for (payload, expected) in cases {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(&payload)
+            .to_request();
+
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 422);
+
+        let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(body, expected);
+    }
+}
+
+#[actix_web::test]
+async fn unknown_fields_are_rejected() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "nmae": "Book", "name": "Book", "description": "Desc", "price": 100 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "error": "unknown field nmae",
+            "field": "nmae",
+            "code": "UNKNOWN_FIELD",
+        })
+    );
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!("/api/products/{}", Uuid::new_v4()))
+        .set_json(serde_json::json!({ "prize": 100 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "error": "unknown field prize",
+            "field": "prize",
+            "code": "UNKNOWN_FIELD",
+        })
+    );
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload("{\"name\": ")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert!(
+        body["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("malformed JSON: "),
+        "{}",
+        body
+    );
+}
+
+#[actix_web::test]
+async fn body_errors_use_the_json_error_format() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(
+            serde_json::json!({ "name": "Book", "description": "x".repeat(2048), "price": 100 }),
+        )
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 413);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "request body must be at most 1024 bytes" })
+    );
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Content-Type", "text/plain"))
+        .set_payload("name=Book")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 415);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "request body must be sent as application/json" })
+    );
+}
+
+#[actix_web::test]
+async fn invalid_dtos_report_every_failing_field() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let expected = serde_json::json!({
+        "error": "name must be 1 to 255 characters",
+        "field": "name",
+        "fields": [
+            { "field": "name", "code": "length", "message": "name must be 1 to 255 characters" },
+            { "field": "price", "code": "range", "message": "price must be a non-negative decimal number" },
+        ],
+    });
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "name": "", "description": "Desc", "price": -5 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 422);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body, expected);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "name": "Book", "description": "Desc", "price": 100 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    let product: serde_json::Value = actix_web::test::read_body_json(resp).await;
+
+    let req = actix_web::test::TestRequest::patch()
+        .uri(&format!(
+            "/api/products/{}",
+            product["id"].as_str().unwrap()
+        ))
+        .set_json(serde_json::json!({ "name": "x".repeat(256), "price": -1 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 422);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body, expected);
+}
+
+#[actix_web::test]
+async fn diff_products_reports_only_differing_fields() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let mut ids = Vec::new();
+    for payload in [
+        serde_json::json!({ "name": "Book", "description": "Same", "price": 100 }),
+        serde_json::json!({ "name": "Book", "description": "Same", "price": 100 }),
+        serde_json::json!({ "name": "Novel", "description": "Same", "price": 250 }),
+    ] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(&payload)
+            .to_request();
+        let resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+        ids.push(resp["id"].as_str().unwrap().to_owned());
+    }
+
+    let identical_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/diff?a={}&b={}", ids[0], ids[1]))
+        .to_request();
+    let identical: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, identical_req).await;
+    assert_eq!(identical, serde_json::json!({}));
+
+    let differing_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/diff?a={}&b={}", ids[0], ids[2]))
+        .to_request();
+    let differing: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, differing_req).await;
+    assert_eq!(
+        differing,
+        serde_json::json!({
+            "name": { "a": "Book", "b": "Novel" },
+            "price": { "a": 100, "b": 250 }
+        })
+    );
+
+    let missing_req = actix_web::test::TestRequest::get()
+        .uri(&format!(
+            "/api/products/diff?a={}&b={}",
+            ids[0],
+            Uuid::new_v4()
+        ))
+        .to_request();
+    let missing_resp = actix_web::test::call_service(&app, missing_req).await;
+    assert_eq!(missing_resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn price_history_returns_series() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let create_req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "name": "Book", "description": "Desc", "price": 100 }))
+        .to_request();
+    let created: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, create_req).await;
+    let id = created["id"].as_str().unwrap();
+
+    let put_req = actix_web::test::TestRequest::put()
+        .uri(&format!("/api/products/{}", id))
+        .set_json(serde_json::json!({ "name": "Book", "description": "Desc", "price": 80 }))
+        .to_request();
+    actix_web::test::call_service(&app, put_req).await;
+
+    let history_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}/price-history", id))
+        .to_request();
+    let history: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, history_req).await;
+
+    let history = history.as_array().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["old_price"], 100);
+    assert_eq!(history[0]["new_price"], 80);
+    assert!(history[0]["changed_at"].is_string());
+
+    let missing_req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products/{}/price-history", Uuid::new_v4()))
+        .to_request();
+    let missing_resp = actix_web::test::call_service(&app, missing_req).await;
+    assert_eq!(missing_resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn find_product_by_slug_returns_product() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let mut created = Vec::new();
+    for _ in 0..2 {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(
+                serde_json::json!({ "name": "A Nice Book", "description": "Desc", "price": 100 }),
+            )
+            .to_request();
+        let resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+        created.push(resp);
+    }
+    assert_eq!(created[0]["slug"], "a-nice-book");
+    assert_eq!(created[1]["slug"], "a-nice-book-2");
+
+    let find_req = actix_web::test::TestRequest::get()
+        .uri("/api/products/slug/a-nice-book-2")
+        .to_request();
+    let found: serde_json::Value = actix_web::test::call_and_read_body_json(&app, find_req).await;
+    assert_eq!(found["id"], created[1]["id"]);
+
+    let missing_req = actix_web::test::TestRequest::get()
+        .uri("/api/products/slug/missing")
+        .to_request();
+    let missing_resp = actix_web::test::call_service(&app, missing_req).await;
+    assert_eq!(missing_resp.status(), 404);
+}
+
+#[actix_web::test]
+async fn add_product_normalizes_price_unit() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let cases = [
+        (serde_json::json!(1299), None, 1299),
+        (serde_json::json!(1299), Some("cents"), 1299),
+        (serde_json::json!(12.99), Some("major"), 1299),
+        (serde_json::json!(12), Some("major"), 1200),
+    ];
+
+    for (price, unit, expected) in cases {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({
+                "name": "Book",
+                "description": "Desc",
+                "price": price,
+                "price_unit": unit
+            }))
+            .to_request();
+        let resp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["price"], expected, "{} {:?}", price, unit);
+    }
+}
+
+#[actix_web::test]
+async fn add_product_rejects_over_precise_price() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let cases = [
+        (
+            serde_json::json!({ "name": "Book", "description": "Desc", "price": 12.999, "price_unit": "major" }),
+            serde_json::json!({ "error": "price must have at most 2 decimal places", "field": "price" }),
+        ),
+        (
+            serde_json::json!({ "name": "Book", "description": "Desc", "price": 12.5 }),
+            serde_json::json!({ "error": "price must have at most 0 decimal places", "field": "price" }),
+        ),
+        (
+            serde_json::json!({ "name": "Book", "description": "Desc", "price": 12, "price_unit": "euros" }),
+            serde_json::json!({ "error": "price unit must be 'cents' or 'major'", "field": "price_unit" }),
+        ),
+    ];
+
+    for (payload, expected) in cases {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(&payload)
+            .to_request();
+
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 422);
+
+        let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(body, expected);
+    }
+
+    // Sent as raw text: as an `f64` this would round to 13.0 and pass.
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload(
+            r#"{ "name": "Book", "description": "Desc", "price": 12.999999999999999999, "price_unit": "major" }"#,
+        )
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 422);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "price must have at most 2 decimal places", "field": "price" })
+    );
+}
+
+#[actix_web::test]
+async fn list_products_pages_and_reports_clamped_values() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for name in ["First", "Second", "Third"] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": 10 }))
+            .to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?limit=2&offset=1")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 2);
+    assert_eq!(body["total"], 3);
+    assert_eq!(body["limit"], 2);
+    assert_eq!(body["offset"], 1);
+
+    let clamped_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?limit=1000")
+        .to_request();
+    let clamped: serde_json::Value =
+        actix_web::test::call_and_read_body_json(&app, clamped_req).await;
+    assert_eq!(clamped["items"].as_array().unwrap().len(), 3);
+    assert_eq!(clamped["limit"], 100);
+    assert_eq!(clamped["offset"], 0);
+}
+
+#[actix_web::test]
+async fn list_products_sorts_by_query() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for (name, price) in [("Banana", 30), ("Apple", 20), ("Cherry", 10)] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": price }))
+            .to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?sort=price&order=desc")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    let names: Vec<_> = body["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Banana", "Apple", "Cherry"]);
+
+    let invalid_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?sort=stock")
+        .to_request();
+    let invalid_resp = actix_web::test::call_service(&app, invalid_req).await;
+    assert_eq!(invalid_resp.status(), 400);
+    let body: serde_json::Value = actix_web::test::read_body_json(invalid_resp).await;
+    assert_eq!(body["field"], "sort");
+}
+
+#[actix_web::test]
+async fn list_products_filters_by_price_range() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for (name, price) in [("Cheap", 10), ("Middle", 20), ("Pricey", 30)] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": price }))
+            .to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    for (query, expected) in [
+        ("min_price=15&max_price=25", vec!["Middle"]),
+        ("min_price=20", vec!["Middle", "Pricey"]),
+        ("max_price=20", vec!["Cheap", "Middle"]),
+    ] {
+        let req = actix_web::test::TestRequest::get()
+            .uri(&format!("/api/products?sort=price&{}", query))
+            .to_request();
+        let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+        let names: Vec<_> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, expected, "{}", query);
+        assert_eq!(body["total"], expected.len(), "{}", query);
+    }
+
+    let inverted_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?min_price=30&max_price=10")
+        .to_request();
+    let inverted_resp = actix_web::test::call_service(&app, inverted_req).await;
+    assert_eq!(inverted_resp.status(), 400);
+    let body: serde_json::Value = actix_web::test::read_body_json(inverted_resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "min_price (30) must not be greater than max_price (10)" })
+    );
+}
+
+#[actix_web::test]
+async fn capabilities_advertise_registered_fields() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/capabilities")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["max_page_size"], 100);
+    assert_eq!(body["max_json_body_bytes"], 65536);
+    assert_eq!(body["price_units"], serde_json::json!(["cents", "major"]));
+    let fields = body["fields"].as_array().unwrap();
+    assert!(fields.contains(&serde_json::json!({
+        "name": "price",
+        "type": "integer",
+        "sortable": true,
+        "filterable": true
+    })));
+    assert!(!fields.iter().any(|field| field["name"] == "description"));
+}
+
+#[actix_web::test]
+async fn capabilities_reflect_configured_limits() {
+    let config = config(&[
+        ("MAX_RESULT_ROWS", "500"),
+        ("MAX_JSON_BODY_BYTES", "2048"),
+        ("MAX_QUERY_PARAMS", "8"),
+        ("MAX_QUERY_LENGTH", "256"),
+        ("DEFAULT_PRICE_UNIT", "major"),
+    ]);
+    let app =
+        actix_web::test::init_service(App::new().app_data(web::Data::new(config.limits())).route(
+            "/api/products/capabilities",
+            web::get().to(rust_backend::handlers::product_handlers::capabilities),
+        ))
+        .await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/capabilities")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["max_result_rows"], 500);
+    assert_eq!(body["max_json_body_bytes"], 2048);
+    assert_eq!(body["max_query_params"], 8);
+    assert_eq!(body["max_query_length"], 256);
+    assert_eq!(body["default_price_unit"], "major");
+}
+
+#[actix_web::test]
+async fn list_products_searches_with_pagination() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for name in ["Big Book", "Notebook", "Pen"] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": 10 }))
+            .to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?q=book&sort=name&limit=1")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["items"].as_array().unwrap().len(), 1);
+    assert_eq!(body["items"][0]["name"], "Big Book");
+
+    let empty_req = actix_web::test::TestRequest::get()
+        .uri("/api/products?q=")
+        .to_request();
+    let empty: serde_json::Value = actix_web::test::call_and_read_body_json(&app, empty_req).await;
+    assert_eq!(empty["total"], 3);
+}
+
+#[actix_web::test]
+async fn export_csv_quotes_fields() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/export.csv")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    assert_eq!(
+        actix_web::test::read_body(resp).await,
+        "id,name,description,price,created_at,updated_at\n"
+    );
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({
+            "name": "Lamp, \"Deluxe\"",
+            "description": "Two\nlines",
+            "price": 1999
+        }))
+        .to_request();
+    let created: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/export.csv")
+        .to_request();
+    let body = actix_web::test::call_and_read_body(&app, req).await;
+    assert!(body.starts_with(b"id,name,description,price,created_at,updated_at\n"));
+    assert!(
+        String::from_utf8_lossy(&body).contains(",\"Lamp, \"\"Deluxe\"\"\",\"Two\nlines\",1999,")
+    );
+
+    let mut reader = csv::Reader::from_reader(body.as_ref());
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(&rows[0][0], created["id"].as_str().unwrap());
+    assert_eq!(&rows[0][1], "Lamp, \"Deluxe\"");
+    assert_eq!(&rows[0][2], "Two\nlines");
+}
+
+#[actix_web::test]
+async fn import_csv_reports_bad_rows_by_line() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let csv = "name,description,price\n\
+               Lamp,\"Desk lamp, brass\",1999\n\
+               ,No name,100\n\
+               Pen,Blue,-5\n\
+               Mug,Tea,12.5\n\
+               Cup,Coffee,250\n";
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products/import?strict=true")
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 422);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["created"], 0);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .to_request();
+    let listed: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listed["total"], 0);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products/import")
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["created"], 2);
+    let errors = body["errors"].as_array().unwrap();
+    let lines: Vec<_> = errors
+        .iter()
+        .map(|e| (e["line"].as_u64().unwrap(), e["field"].as_str().unwrap()))
+        .collect();
+    assert_eq!(lines, [(3, "name"), (4, "price"), (5, "price")]);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?sort=name&order=asc")
+        .to_request();
+    let listed: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listed["items"][0]["name"], "Cup");
+    assert_eq!(listed["items"][1]["name"], "Lamp");
+    assert_eq!(listed["items"][1]["description"], "Desk lamp, brass");
+    assert_eq!(listed["items"][1]["price"], 1999);
+}
+
+#[actix_web::test]
+async fn import_csv_takes_optional_tags_and_sku() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let csv = "name,description,price,tags,sku\n\
+               Lamp,Desk lamp,1999,lighting; desk,\" lmp-1 \"\n\
+               Pen,Blue,100,,\n";
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products/import")
+        .insert_header(("Content-Type", "text/csv"))
+        .set_payload(csv)
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["created"], 2);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/by-sku/LMP-1")
+        .to_request();
+    let lamp: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(lamp["name"], "Lamp");
+    assert_eq!(lamp["tags"], serde_json::json!(["lighting", "desk"]));
+}
+
+#[actix_web::test]
+async fn import_csv_checks_content_type_and_header() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products/import")
+        .set_json(serde_json::json!([{ "name": "Lamp" }]))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 415);
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products/import")
+        .insert_header(("Content-Type", "text/csv; charset=utf-8"))
+        .set_payload("name,price\nLamp,1999\n")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["error"], "CSV header has no 'description' column");
+}
+
+#[actix_web::test]
+async fn list_products_streams_every_match() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?stream=true")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(actix_web::test::read_body(resp).await, "[]");
+
+    for name in ["Big Book", "Notebook", "Pen"] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": 10 }))
+            .to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?stream=true&q=book&sort=name&order=desc")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    let names: Vec<_> = body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Notebook", "Big Book"]);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?stream=true&limit=1")
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn list_products_pages_by_cursor() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let add = |name: &str| {
+        actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": name, "description": "Desc", "price": 10 }))
+            .to_request()
+    };
+
+    for name in ["A", "B", "C", "D", "E"] {
+        actix_web::test::call_service(&app, add(name)).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products?limit=2")
+        .to_request();
+    let first: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(first["items"][0]["name"], "E");
+    assert_eq!(first["items"][1]["name"], "D");
+
+    // Offset paging would now see "D" again; the cursor doesn't.
+    actix_web::test::call_service(&app, add("F")).await;
+
+    let mut names = Vec::new();
+    let mut cursor = first["next_cursor"].as_str().unwrap().to_owned();
+    loop {
+        let req = actix_web::test::TestRequest::get()
+            .uri(&format!("/api/products?limit=2&after={}", cursor))
+            .to_request();
+        let page: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+        for item in page["items"].as_array().unwrap() {
+            names.push(item["name"].as_str().unwrap().to_owned());
+        }
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = next.to_owned(),
+            None => break,
+        }
+    }
+    assert_eq!(names, ["C", "B", "A"]);
+
+    for uri in [
+        "/api/products?after=nope",
+        &format!("/api/products?after={}&offset=2", cursor),
+        &format!("/api/products?after={}&sort=name", cursor),
+    ] {
+        let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400, "{}", uri);
+        let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(body["field"], "after");
+    }
+}
+
+#[actix_web::test]
+async fn product_stats_aggregate_prices() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/stats")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "count": 0,
+            "min_price": null,
+            "max_price": null,
+            "average_price": null
+        })
+    );
+
+    for price in [100, 250, 251] {
+        let req = actix_web::test::TestRequest::post()
+            .uri("/api/products")
+            .set_json(serde_json::json!({ "name": "Item", "description": "Desc", "price": price }))
+            .to_request();
+        actix_web::test::call_service(&app, req).await;
+    }
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products/stats")
+        .to_request();
+    let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "count": 3,
+            "min_price": 100,
+            "max_price": 251,
+            "average_price": 200
+        })
+    );
+}
+
+#[actix_web::test]
+async fn service_validation_errors_name_the_field() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::post()
+        .uri("/api/products")
+        .set_json(serde_json::json!({ "name": "   ", "description": "Desc", "price": 10 }))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 422);
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        serde_json::json!({ "error": "name must not be empty", "field": "name" })
+    );
+}
+
+#[actix_web::test]
+async fn list_products_rejects_oversized_query_strings() {
+    let app = actix_web::test::init_service(test_app()).await;
+    let params = |count: usize| {
+        (0..count)
+            .map(|i| format!("p{}=a", i))
+            .collect::<Vec<_>>()
+            .join("&")
+    };
+    let too_many = params(QueryLimits::DEFAULT_MAX_PARAMS + 1);
+    let too_long = format!("q={}", "a".repeat(QueryLimits::DEFAULT_MAX_LENGTH));
+
+    for query in [too_many, too_long] {
+        let req = actix_web::test::TestRequest::get()
+            .uri(&format!("/api/products?{}", query))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+        assert!(
+            body["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("query string must")
+        );
+    }
+
+    let within = params(QueryLimits::DEFAULT_MAX_PARAMS);
+    let req = actix_web::test::TestRequest::get()
+        .uri(&format!("/api/products?{}", within))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+}
@@ -1,32 +1,58 @@
-use sqlx::PgPool;
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use sqlx::{
+    PgPool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
 use uuid::Uuid;
 
 use rust_backend::{
-    application::product_service::ProductRepository,
-    repositories::product_repository::PgProductRepository,
+    application::{
+        filtering::ProductFilter,
+        pagination::{Cursor, Page},
+        product_service::{
+            ClassifyError, ProductRepository, ProductService, ProductServiceError, Reservation,
+        },
+        sorting::Sort,
+    },
+    domain::{price::Price, product::NewProduct},
+    repositories::product_repository::{
+        PgProductRepository, RepositoryError, with_statement_timeout,
+    },
 };
 
+fn price(cents: u32) -> Price {
+    Price::new(cents).unwrap()
+}
+
 #[sqlx::test(migrations = "./migrations")]
 async fn create_product_works(pool: PgPool) {
     let repo = PgProductRepository::new(pool);
 
     let product = repo
-        .create("Book".into(), "A nice book".into(), 100)
+        .create(
+            "Book".into(),
+            "A nice book".into(),
+            price(100),
+            Vec::new(),
+            None,
+        )
         .await
         .unwrap();
 
     assert_eq!(product.name, "Book");
-    assert_eq!(product.price, 100);
+    assert_eq!(product.price, price(100));
 }
 
 #[sqlx::test(migrations = "./migrations")]
 async fn read_all_returns_products(pool: PgPool) {
     let repo = PgProductRepository::new(pool);
 
-    repo.create("Item A".into(), "Desc".into(), 10)
+    repo.create("Item A".into(), "Desc".into(), price(10), Vec::new(), None)
         .await
         .unwrap();
-    repo.create("Item B".into(), "Desc".into(), 20)
+    repo.create("Item B".into(), "Desc".into(), price(20), Vec::new(), None)
         .await
         .unwrap();
 
@@ -35,47 +61,878 @@ async fn read_all_returns_products(pool: PgPool) {
     assert_eq!(products.len(), 2);
 }
 
+#[sqlx::test(migrations = "./migrations")]
+async fn read_all_aborts_over_row_ceiling(pool: PgPool) {
+    let repo = PgProductRepository::new(pool).with_max_rows(2);
+
+    repo.create("Item A".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    repo.create("Item B".into(), "Desc".into(), price(20), Vec::new(), None)
+        .await
+        .unwrap();
+    assert_eq!(repo.read_all().await.unwrap().len(), 2);
+
+    repo.create("Item C".into(), "Desc".into(), price(30), Vec::new(), None)
+        .await
+        .unwrap();
+    let result = repo.read_all().await;
+
+    assert!(matches!(
+        result,
+        Err(RepositoryError::TooManyRows { limit: 2 })
+    ));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn read_sorted_aborts_over_row_ceiling(pool: PgPool) {
+    let repo = PgProductRepository::new(pool).with_max_rows(2);
+    let filter = ProductFilter::default();
+    let read = || repo.read_sorted(&filter, Sort::default(), Page::new(Some(20), None), false);
+
+    for (name, cents) in [("Item A", 10), ("Item B", 20)] {
+        repo.create(name.into(), "Desc".into(), price(cents), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+    assert_eq!(read().await.unwrap().len(), 2);
+
+    repo.create("Item C".into(), "Desc".into(), price(30), Vec::new(), None)
+        .await
+        .unwrap();
+    assert!(matches!(
+        read().await,
+        Err(RepositoryError::TooManyRows { limit: 2 })
+    ));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn create_many_gives_duplicate_names_distinct_slugs(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+    let lamp = || NewProduct {
+        name: "Lamp".into(),
+        description: "Desk lamp".into(),
+        price: price(1999),
+        tags: Vec::new(),
+        sku: None,
+    };
+
+    let created = repo.create_many(vec![lamp(), lamp()]).await.unwrap();
+
+    let slugs: Vec<_> = created.iter().map(|p| p.slug.as_str()).collect();
+    assert_eq!(slugs, ["lamp", "lamp-2"]);
+    assert_eq!(repo.read_all().await.unwrap().len(), 2);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn create_many_keeps_tags_and_skus(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    repo.create_many(vec![NewProduct {
+        name: "Lamp".into(),
+        description: "Desk lamp".into(),
+        price: price(1999),
+        tags: vec!["lighting".into()],
+        sku: Some("LMP-1".into()),
+    }])
+    .await
+    .unwrap();
+
+    let lamp = repo.find_by_sku("LMP-1").await.unwrap().unwrap();
+    assert_eq!(lamp.tags, ["lighting"]);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn stream_sorted_yields_every_match_in_order(pool: PgPool) {
+    let repo = PgProductRepository::new(pool).with_max_rows(1);
+
+    for (name, cents) in [("Item A", 30), ("Item B", 10), ("Item C", 20), ("Other", 5)] {
+        repo.create(name.into(), "Desc".into(), price(cents), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+
+    let filter = ProductFilter::default().with_search(Some("item".into()));
+    let sort = Sort::parse(Some("price"), None).unwrap();
+    let names: Vec<String> = repo
+        .stream_sorted(&filter, sort, false)
+        .map_ok(|product| product.name)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(names, ["Item B", "Item C", "Item A"]);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn keyset_pages_break_updated_at_ties_by_id(pool: PgPool) {
+    let repo = PgProductRepository::new(pool.clone());
+
+    for name in ["Item A", "Item B", "Item C", "Item D", "Item E"] {
+        repo.create(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+    sqlx::query("UPDATE products SET updated_at = '2025-01-01T00:00:00Z'")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let filter = ProductFilter::default();
+    let mut seen = Vec::new();
+    let mut after = None;
+    loop {
+        let page = Page::new(Some(2), None).with_after(after);
+        let products = repo
+            .read_sorted(&filter, Sort::default(), page, false)
+            .await
+            .unwrap();
+        let Some(last) = products.last() else {
+            break;
+        };
+        after = Some(Cursor::from(last));
+        seen.extend(products.iter().map(|p| p.id));
+    }
+
+    let mut expected = seen.clone();
+    expected.sort();
+    expected.reverse();
+    assert_eq!(seen, expected);
+    assert_eq!(seen.len(), 5);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn stats_skip_deleted_products(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let stats = repo.stats().await.unwrap();
+    assert_eq!(stats.count, 0);
+    assert_eq!(stats.min_price, None);
+    assert_eq!(stats.average_price, None);
+
+    for cents in [100, 201, 300] {
+        repo.create("Item".into(), "Desc".into(), price(cents), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+    let deleted = repo
+        .create("Gone".into(), "Desc".into(), price(9999), Vec::new(), None)
+        .await
+        .unwrap();
+    repo.delete(deleted.id).await.unwrap();
+
+    let stats = repo.stats().await.unwrap();
+    assert_eq!(stats.count, 3);
+    assert_eq!(stats.min_price, Some(price(100)));
+    assert_eq!(stats.max_price, Some(price(300)));
+    assert_eq!(stats.average_price, Some(price(200)));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn stream_all_has_no_row_ceiling(pool: PgPool) {
+    let repo = PgProductRepository::new(pool).with_max_rows(1);
+
+    for name in ["Item A", "Item B", "Item C"] {
+        repo.create(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+    let deleted = repo
+        .create("Item D".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    repo.delete(deleted.id).await.unwrap();
+
+    let names: Vec<String> = repo
+        .stream_all()
+        .map_ok(|product| product.name)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(names, ["Item A", "Item B", "Item C"]);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn read_sorted_defaults_to_most_recent_first(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    for name in ["Item A", "Item B", "Item C"] {
+        repo.create(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+
+    let first = repo
+        .read_sorted(
+            &ProductFilter::default(),
+            Sort::default(),
+            Page::new(Some(2), None),
+            false,
+        )
+        .await
+        .unwrap();
+    let rest = repo
+        .read_sorted(
+            &ProductFilter::default(),
+            Sort::default(),
+            Page::new(Some(2), Some(2)),
+            false,
+        )
+        .await
+        .unwrap();
+
+    let names: Vec<_> = first.iter().chain(&rest).map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Item C", "Item B", "Item A"]);
+    assert_eq!(
+        repo.count(&ProductFilter::default(), false).await.unwrap(),
+        3
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn read_sorted_orders_by_requested_field(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    for (name, cents) in [("Banana", 30), ("Apple", 20), ("Cherry", 10)] {
+        repo.create(name.into(), "Desc".into(), price(cents), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+
+    let by_price = repo
+        .read_sorted(
+            &ProductFilter::default(),
+            Sort::parse(Some("price"), None).unwrap(),
+            Page::default(),
+            false,
+        )
+        .await
+        .unwrap();
+    let names: Vec<_> = by_price.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Cherry", "Apple", "Banana"]);
+
+    let by_name = repo
+        .read_sorted(
+            &ProductFilter::default(),
+            Sort::parse(Some("name"), Some("desc")).unwrap(),
+            Page::default(),
+            false,
+        )
+        .await
+        .unwrap();
+    let names: Vec<_> = by_name.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Cherry", "Banana", "Apple"]);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn read_sorted_filters_by_price_range(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    for (name, cents) in [("Cheap", 10), ("Middle", 20), ("Pricey", 30)] {
+        repo.create(name.into(), "Desc".into(), price(cents), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+
+    for (filter, expected) in [
+        ((Some(15), Some(25)), vec!["Middle"]),
+        ((Some(20), None), vec!["Middle", "Pricey"]),
+        ((None, Some(20)), vec!["Cheap", "Middle"]),
+    ] {
+        let filter = ProductFilter::default()
+            .with_range("price", filter.0, filter.1)
+            .unwrap();
+        let products = repo
+            .read_sorted(
+                &filter,
+                Sort::parse(Some("price"), None).unwrap(),
+                Page::default(),
+                false,
+            )
+            .await
+            .unwrap();
+        let names: Vec<_> = products.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, expected);
+        assert_eq!(
+            repo.count(&filter, false).await.unwrap(),
+            expected.len() as u64
+        );
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn read_sorted_searches_names_literally(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    for name in ["Big Book", "Notebook", "Pen", "100% Cotton", "1000 Cotton"] {
+        repo.create(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+
+    for (query, expected) in [
+        ("BOOK", vec!["Big Book", "Notebook"]),
+        ("100%", vec!["100% Cotton"]),
+        ("_", vec![]),
+        (
+            "",
+            vec!["100% Cotton", "1000 Cotton", "Big Book", "Notebook", "Pen"],
+        ),
+    ] {
+        let filter = ProductFilter::default().with_search(Some(query.into()));
+        let products = repo
+            .read_sorted(&filter, Sort::default(), Page::default(), false)
+            .await
+            .unwrap();
+        // Sorted here rather than by the query, whose order depends on collation.
+        let mut names: Vec<_> = products.iter().map(|p| p.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, expected, "{}", query);
+        assert_eq!(
+            repo.count(&filter, false).await.unwrap(),
+            expected.len() as u64
+        );
+    }
+}
+
 #[sqlx::test(migrations = "./migrations")]
 async fn read_one_returns_none_if_missing(pool: PgPool) {
     let repo = PgProductRepository::new(pool);
 
-    let result = repo.read_one(Uuid::new_v4()).await.unwrap();
+    let result = repo.read_one(Uuid::new_v4().into(), false).await.unwrap();
 
     assert!(result.is_none());
 }
 
+#[sqlx::test(migrations = "./migrations")]
+async fn read_updated_within_respects_window(pool: PgPool) {
+    let repo = PgProductRepository::new(pool.clone());
+
+    let inside = repo
+        .create("Inside".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    let outside = repo
+        .create("Outside".into(), "Desc".into(), price(20), Vec::new(), None)
+        .await
+        .unwrap();
+
+    sqlx::query("UPDATE products SET updated_at = now() - $1 WHERE id = $2")
+        .bind(Duration::from_secs(59 * 60))
+        .bind(inside.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE products SET updated_at = now() - $1 WHERE id = $2")
+        .bind(Duration::from_secs(61 * 60))
+        .bind(outside.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let recent = repo
+        .read_updated_within(Duration::from_secs(60 * 60))
+        .await
+        .unwrap();
+
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0].id, inside.id);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn negative_stored_price_is_an_error(pool: PgPool) {
+    let repo = PgProductRepository::new(pool.clone());
+
+    let product = repo
+        .create("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE products SET price = -1 WHERE id = $1")
+        .bind(product.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let result = repo.read_one(product.id, false).await;
+    assert!(matches!(
+        result,
+        Err(RepositoryError::Sqlx(sqlx::Error::ColumnDecode { .. }))
+    ));
+}
+
 #[sqlx::test(migrations = "./migrations")]
 async fn update_product_works(pool: PgPool) {
     let repo = PgProductRepository::new(pool);
 
     let product = repo
-        .create("Old".into(), "Old desc".into(), 10)
+        .create("Old".into(), "Old desc".into(), price(10), Vec::new(), None)
         .await
         .unwrap();
 
     let updated = repo
-        .update(product.id, "New".into(), "New desc".into(), 20)
+        .update(
+            product.id,
+            NewProduct {
+                name: "New".into(),
+                description: "New desc".into(),
+                price: price(20),
+                tags: Vec::new(),
+                sku: None,
+            },
+            None,
+        )
         .await
         .unwrap()
         .unwrap();
 
     assert_eq!(updated.name, "New");
-    assert_eq!(updated.price, 20);
+    assert_eq!(updated.price, price(20));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn update_records_price_change(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let product = repo
+        .create("Book".into(), "Desc".into(), price(100), Vec::new(), None)
+        .await
+        .unwrap();
+
+    repo.update(
+        product.id,
+        NewProduct {
+            name: "Book".into(),
+            description: "New desc".into(),
+            price: price(100),
+            tags: Vec::new(),
+            sku: None,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(
+        repo.read_price_history(product.id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    repo.update(
+        product.id,
+        NewProduct {
+            name: "Book".into(),
+            description: "New desc".into(),
+            price: price(150),
+            tags: Vec::new(),
+            sku: None,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+    let history = repo.read_price_history(product.id).await.unwrap();
+
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].old_price, price(100));
+    assert_eq!(history[0].new_price, price(150));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn patch_updates_only_given_fields(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let product = repo
+        .create("Book".into(), "Desc".into(), price(100), Vec::new(), None)
+        .await
+        .unwrap();
+
+    let patched = repo
+        .patch(product.id, None, Some("New desc".into()), None, None, None)
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(patched.name, "Book");
+    assert_eq!(patched.description, "New desc");
+    assert_eq!(patched.price, price(100));
+    assert!(patched.updated_at > product.updated_at);
+    assert!(
+        repo.patch(
+            Uuid::new_v4().into(),
+            None,
+            None,
+            Some(price(1)),
+            None,
+            None
+        )
+        .await
+        .unwrap()
+        .is_none()
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn create_suffixes_colliding_slugs(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let first = repo
+        .create("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    let second = repo
+        .create("book!".into(), "Desc".into(), price(20), Vec::new(), None)
+        .await
+        .unwrap();
+
+    assert_eq!(first.slug, "book");
+    assert_eq!(second.slug, "book-2");
+
+    let found = repo.read_by_slug("book-2").await.unwrap().unwrap();
+    assert_eq!(found.id, second.id);
+    assert!(repo.read_by_slug("book-3").await.unwrap().is_none());
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn unique_violation_is_a_conflict(pool: PgPool) {
+    sqlx::query("CREATE UNIQUE INDEX products_name_idx ON products (name)")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let repo = PgProductRepository::new(pool);
+
+    repo.create("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    let result = repo
+        .create("Book".into(), "Desc".into(), price(20), Vec::new(), None)
+        .await;
+
+    let Err(error) = result else {
+        panic!("duplicate name was accepted");
+    };
+    assert!(matches!(error, RepositoryError::Conflict(_)));
+    assert!(error.is_conflict());
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn update_regenerates_slug_only_when_configured(pool: PgPool) {
+    let keeping = PgProductRepository::new(pool.clone());
+    let product = keeping
+        .create(
+            "Old Name".into(),
+            "Desc".into(),
+            price(10),
+            Vec::new(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let renamed = keeping
+        .update(
+            product.id,
+            NewProduct {
+                name: "New Name".into(),
+                description: "Desc".into(),
+                price: price(10),
+                tags: Vec::new(),
+                sku: None,
+            },
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(renamed.slug, "old-name");
+
+    let regenerating = PgProductRepository::new(pool).with_slug_regeneration(true);
+    regenerating
+        .create(
+            "Newer Name".into(),
+            "Desc".into(),
+            price(10),
+            Vec::new(),
+            None,
+        )
+        .await
+        .unwrap();
+    let renamed = regenerating
+        .update(
+            product.id,
+            NewProduct {
+                name: "Newer Name".into(),
+                description: "Desc".into(),
+                price: price(10),
+                tags: Vec::new(),
+                sku: None,
+            },
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(renamed.slug, "newer-name-2");
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn slow_statement_times_out(pool_options: PgPoolOptions, connect_options: PgConnectOptions) {
+    let pool = pool_options
+        .connect_with(with_statement_timeout(
+            connect_options,
+            Duration::from_millis(100),
+        ))
+        .await
+        .unwrap();
+    let repo = PgProductRepository::new(pool.clone());
+
+    let product = repo
+        .create("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+
+    // Holding the row lock makes the update wait until the timeout fires.
+    let mut blocker = pool.begin().await.unwrap();
+    sqlx::query("SELECT 1 FROM products WHERE id = $1 FOR UPDATE")
+        .bind(product.id)
+        .execute(&mut *blocker)
+        .await
+        .unwrap();
+
+    let result = repo
+        .update(
+            product.id,
+            NewProduct {
+                name: "Book".into(),
+                description: "Desc".into(),
+                price: price(20),
+                tags: Vec::new(),
+                sku: None,
+            },
+            None,
+        )
+        .await;
+
+    let Err(error) = result else {
+        panic!("update should have timed out");
+    };
+    assert!(matches!(error, RepositoryError::Timeout(_)));
+    assert!(error.is_timeout());
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn update_with_stale_version_is_rejected(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let product = repo
+        .create("Book".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    assert_eq!(product.version, 1);
+
+    let updated = repo
+        .patch(product.id, None, None, Some(price(20)), None, Some(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.version, 2);
+
+    let result = repo
+        .update(
+            product.id,
+            NewProduct {
+                name: "Book".into(),
+                description: "Desc".into(),
+                price: price(30),
+                tags: Vec::new(),
+                sku: None,
+            },
+            Some(1),
+        )
+        .await;
+    let Err(error) = result else {
+        panic!("stale update was applied");
+    };
+    assert!(error.is_stale());
+    let stored = repo.read_one(product.id, false).await.unwrap().unwrap();
+    assert_eq!(stored.price, price(20));
+    assert_eq!(stored.version, 2);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn concurrent_patches_keep_price_history_chained(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let product = repo
+        .create("Book".into(), "Desc".into(), price(1), Vec::new(), None)
+        .await
+        .unwrap();
+    let patch = |cents| repo.patch(product.id, None, None, Some(price(cents)), None, None);
+    tokio::try_join!(patch(2), patch(3), patch(4), patch(5)).unwrap();
+
+    // Each change must start from the price the previous one left behind;
+    // otherwise two patches read the same old price and one was lost.
+    let history = repo.read_price_history(product.id).await.unwrap();
+    assert_eq!(history.len(), 4);
+    let mut current = price(1);
+    for change in history {
+        assert_eq!(change.old_price, current);
+        current = change.new_price;
+    }
+    let stored = repo.read_one(product.id, false).await.unwrap().unwrap();
+    assert_eq!(stored.price, current);
 }
 
 #[sqlx::test(migrations = "./migrations")]
 async fn delete_product_works(pool: PgPool) {
     let repo = PgProductRepository::new(pool);
 
-    let product = repo.create("Temp".into(), "Temp".into(), 1).await.unwrap();
+    let product = repo
+        .create("Temp".into(), "Temp".into(), price(1), Vec::new(), None)
+        .await
+        .unwrap();
 
     let deleted = repo.delete(product.id).await.unwrap();
     assert!(deleted);
 
-    let found = repo.read_one(product.id).await.unwrap();
+    let found = repo.read_one(product.id, false).await.unwrap();
     assert!(found.is_none());
 }
 
+#[sqlx::test(migrations = "./migrations")]
+async fn restore_bumps_updated_at_unless_configured_not_to(pool: PgPool) {
+    for touch in [true, false] {
+        let repo = PgProductRepository::new(pool.clone()).with_restore_touch(touch);
+        let product = repo
+            .create("Temp".into(), "Temp".into(), price(1), Vec::new(), None)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE products SET updated_at = '2025-01-01T00:00:00Z' WHERE id = $1")
+            .bind(product.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(repo.delete(product.id).await.unwrap());
+        let deleted = repo.read_one(product.id, true).await.unwrap().unwrap();
+
+        let restored = repo.restore(product.id).await.unwrap().unwrap();
+
+        if touch {
+            assert!(restored.updated_at > deleted.updated_at);
+        } else {
+            assert_eq!(restored.updated_at, deleted.updated_at);
+        }
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn deleted_product_keeps_history_and_can_be_restored(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let product = repo
+        .create("Temp".into(), "Temp".into(), price(1), Vec::new(), None)
+        .await
+        .unwrap();
+    repo.update(
+        product.id,
+        NewProduct {
+            name: "Temp".into(),
+            description: "Temp".into(),
+            price: price(2),
+            tags: Vec::new(),
+            sku: None,
+        },
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(repo.delete(product.id).await.unwrap());
+    assert!(!repo.delete(product.id).await.unwrap());
+    assert_eq!(
+        repo.count(&ProductFilter::default(), false).await.unwrap(),
+        0
+    );
+    assert!(repo.read_by_slug("temp").await.unwrap().is_none());
+    let updated = repo
+        .update(
+            product.id,
+            NewProduct {
+                name: "Temp".into(),
+                description: "Temp".into(),
+                price: price(3),
+                tags: Vec::new(),
+                sku: None,
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(updated.is_none());
+
+    let restored = repo.restore(product.id).await.unwrap().unwrap();
+    assert_eq!(restored.price, price(2));
+    assert!(restored.deleted_at.is_none());
+    assert!(repo.restore(product.id).await.unwrap().is_none());
+    assert_eq!(repo.read_price_history(product.id).await.unwrap().len(), 1);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn delete_many_counts_only_live_products(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let mut ids = Vec::new();
+    for name in ["A", "B", "C"] {
+        let product = repo
+            .create(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+        ids.push(product.id);
+    }
+    repo.delete(ids[0]).await.unwrap();
+
+    let targets = [ids[0], ids[1], ids[2], Uuid::new_v4().into()];
+    assert_eq!(repo.delete_many(&targets).await.unwrap(), 2);
+    assert_eq!(
+        repo.count(&ProductFilter::default(), false).await.unwrap(),
+        0
+    );
+    assert_eq!(repo.delete_many(&[]).await.unwrap(), 0);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn include_deleted_lists_deleted_products(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    for name in ["Kept", "Gone"] {
+        repo.create(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+    let gone = repo.read_by_slug("gone").await.unwrap().unwrap();
+    repo.delete(gone.id).await.unwrap();
+
+    let filter = ProductFilter::default().with_search(Some("e".into()));
+    for (include_deleted, expected) in [(false, vec!["Kept"]), (true, vec!["Gone", "Kept"])] {
+        let products = repo
+            .read_sorted(
+                &filter,
+                Sort::parse(Some("name"), None).unwrap(),
+                Page::default(),
+                include_deleted,
+            )
+            .await
+            .unwrap();
+        let names: Vec<_> = products.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, expected);
+        assert_eq!(
+            repo.count(&filter, include_deleted).await.unwrap(),
+            expected.len() as u64
+        );
+    }
+}
+
 #[sqlx::test(migrations = "./migrations")]
 async fn service_maps_not_found_correctly(pool: PgPool) {
     use rust_backend::{
@@ -86,7 +943,190 @@ async fn service_maps_not_found_correctly(pool: PgPool) {
     let repo = PgProductRepository::new(pool);
     let service = ProductService::new(repo);
 
-    let result = service.find(Uuid::new_v4()).await;
+    let result = service.find(Uuid::new_v4().into(), false).await;
 
     matches!(result, Err(ProductServiceError::NotFound));
 }
+
+#[sqlx::test(migrations = "./migrations")]
+async fn products_can_be_filtered_by_tag(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let lamp = repo
+        .create(
+            "Lamp".into(),
+            "Desc".into(),
+            price(10),
+            vec!["sale".into(), "new".into()],
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(lamp.tags, ["sale", "new"]);
+    repo.create(
+        "Chair".into(),
+        "Desc".into(),
+        price(20),
+        vec!["new".into()],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let sale = ProductFilter::default().with_tag(Some("sale".into()));
+    let products = repo
+        .read_sorted(&sale, Sort::default(), Page::new(None, None), false)
+        .await
+        .unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name, "Lamp");
+
+    let new = ProductFilter::default().with_tag(Some("new".into()));
+    assert_eq!(repo.count(&new, false).await.unwrap(), 2);
+
+    let lamp = repo
+        .patch(lamp.id, None, None, None, Some(Vec::new()), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(lamp.tags.is_empty());
+    assert_eq!(repo.count(&sale, false).await.unwrap(), 0);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn concurrent_reservations_never_oversell(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+
+    let product = repo
+        .create("Lamp".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    repo.set_stock(product.id, 3).await.unwrap().unwrap();
+
+    let reserve = || repo.reserve(product.id, 1);
+    let (a, b, c, d, e, f) = tokio::join!(
+        reserve(),
+        reserve(),
+        reserve(),
+        reserve(),
+        reserve(),
+        reserve()
+    );
+    let results = [a, b, c, d, e, f];
+    let reserved = results
+        .iter()
+        .filter(|result| matches!(result, Ok(Reservation::Reserved(_))))
+        .count();
+    let refused = results
+        .iter()
+        .filter(|result| matches!(result, Ok(Reservation::Insufficient { available: 0 })))
+        .count();
+    assert_eq!((reserved, refused), (3, 3));
+
+    let product = repo.read_one(product.id, false).await.unwrap().unwrap();
+    assert_eq!(product.stock, 0);
+    assert!(matches!(
+        repo.reserve(Uuid::new_v4().into(), 1).await.unwrap(),
+        Reservation::NotFound
+    ));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn skus_are_normalized_and_unique(pool: PgPool) {
+    let service = ProductService::new(PgProductRepository::new(pool));
+
+    let lamp = service
+        .add(
+            "Lamp".into(),
+            "Desc".into(),
+            price(10),
+            Vec::new(),
+            Some(" lmp-01 ".into()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(lamp.sku.as_deref(), Some("LMP-01"));
+
+    // Products without a SKU don't clash with each other.
+    for name in ["Chair", "Table"] {
+        service
+            .add(name.into(), "Desc".into(), price(10), Vec::new(), None)
+            .await
+            .unwrap();
+    }
+
+    let result = service
+        .add(
+            "Other lamp".into(),
+            "Desc".into(),
+            price(10),
+            Vec::new(),
+            Some("LMP-01".into()),
+        )
+        .await;
+    assert!(matches!(
+        result,
+        Err(ProductServiceError::Repository(error)) if error.is_conflict()
+    ));
+
+    let found = service.find_by_sku("lmp-01").await.unwrap();
+    assert_eq!(found.id, lamp.id);
+    assert!(matches!(
+        service.find_by_sku("nope").await,
+        Err(ProductServiceError::NotFound)
+    ));
+
+    // A deleted product gives its SKU up, and can't be restored while
+    // another product holds it.
+    service.remove(lamp.id).await.unwrap();
+    let new_lamp = service
+        .add(
+            "New lamp".into(),
+            "Desc".into(),
+            price(10),
+            Vec::new(),
+            Some("LMP-01".into()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(service.find_by_sku("LMP-01").await.unwrap().id, new_lamp.id);
+    assert!(matches!(
+        service.restore(lamp.id).await,
+        Err(ProductServiceError::Repository(error)) if error.is_conflict()
+    ));
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn a_missing_sku_is_set_once_under_the_row_lock(pool: PgPool) {
+    let repo = PgProductRepository::new(pool);
+    let product = repo
+        .create("Lamp".into(), "Desc".into(), price(10), Vec::new(), None)
+        .await
+        .unwrap();
+    let with_sku = |sku: Option<&str>| NewProduct {
+        name: "Lamp".into(),
+        description: "Desc".into(),
+        price: price(10),
+        tags: Vec::new(),
+        sku: sku.map(String::from),
+    };
+
+    let updated = repo
+        .update(product.id, with_sku(Some("LMP-9")), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.sku.as_deref(), Some("LMP-9"));
+
+    let result = repo
+        .update(product.id, with_sku(Some("LMP-10")), None)
+        .await;
+    assert!(matches!(result, Err(error) if error.is_immutable()));
+
+    let kept = repo
+        .update(product.id, with_sku(None), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kept.sku.as_deref(), Some("LMP-9"));
+}
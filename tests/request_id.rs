@@ -0,0 +1,58 @@
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, middleware::from_fn, web};
+use uuid::Uuid;
+
+use rust_backend::middleware::request_id::{REQUEST_ID_HEADER, RequestId, request_id};
+
+fn test_app() -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new().wrap(from_fn(request_id)).route(
+        "/api/products",
+        web::get().to(|req: HttpRequest| async move {
+            let id = req.extensions().get::<RequestId>().cloned().unwrap();
+            assert_eq!(RequestId::current(), id.as_str());
+            HttpResponse::Ok().body(id.as_str().to_owned())
+        }),
+    )
+}
+
+#[actix_web::test]
+async fn incoming_id_is_kept_and_echoed() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/products")
+        .insert_header((REQUEST_ID_HEADER, "abc-123"))
+        .to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+
+    assert_eq!(resp.headers().get(REQUEST_ID_HEADER).unwrap(), "abc-123");
+    assert_eq!(actix_web::test::read_body(resp).await, "abc-123");
+}
+
+#[actix_web::test]
+async fn missing_or_unusable_id_is_generated() {
+    let app = actix_web::test::init_service(test_app()).await;
+
+    for incoming in [None, Some("x".repeat(200)), Some("has space".to_owned())] {
+        let mut req = actix_web::test::TestRequest::get().uri("/api/products");
+        if let Some(incoming) = incoming {
+            req = req.insert_header((REQUEST_ID_HEADER, incoming));
+        }
+        let resp = actix_web::test::call_service(&app, req.to_request()).await;
+
+        let id = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert!(id.to_str().unwrap().parse::<Uuid>().is_ok());
+    }
+}
+
+#[test]
+fn no_current_id_outside_a_request() {
+    assert_eq!(RequestId::current(), "-");
+}